@@ -0,0 +1,15 @@
+use deft_reversi_engine::*;
+
+/// 評価関数を `out_path` の拡張子に応じた形式で保存する
+pub fn convert_eval(evaluator: &Evaluator, out_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if out_path.ends_with(".json") {
+        evaluator.write_file(out_path)?;
+    } else {
+        evaluator.write_binary_file(out_path)?;
+    }
+    println!(
+        "Converted evaluator (version {}) : {}",
+        evaluator.version, out_path
+    );
+    Ok(())
+}
@@ -2,14 +2,22 @@ mod solve;
 mod self_play;
 mod play;
 mod perft;
+mod convert_eval;
 
 use crate::play::*;
 use crate::solve::*;
 use crate::self_play::*;
 use crate::perft::*;
+use crate::convert_eval::*;
 use clap::Parser;
+use std::sync::Arc;
+
+use deft_reversi_engine::{current_backend, init_backend, set_backend, Evaluator, MpcTable};
 
 const DEFAULT_LEVEL: u8 = 10;
+/// `--eval-path` を省略したときに読み込む評価関数 (`builtin-eval` feature が無効な場合)
+#[cfg(not(feature = "builtin-eval"))]
+const DEFAULT_EVAL_PATH: &str = "../data/eval/eval.json";
 
 
 /// Reversi games
@@ -20,14 +28,43 @@ struct Args {
     #[arg(short, long)]
     solve: Option<String>,
 
- /// Path to read eval weight from
+    /// Path to read eval weight from (JSON or binary).
+    /// Defaults to the builtin evaluator if built with the "builtin-eval" feature, "../data/eval/eval.json" otherwise
     #[arg(short, long)]
     eval_path: Option<String>,
 
+    /// Use an evaluator whose weights are all zero (for benchmarking the search only)
+    #[arg(long, conflicts_with = "eval_path")]
+    zero_eval: bool,
+
+    /// MPC parameter table written by "deft-reversi-learn mpc" (default: the compiled-in parameters)
+    #[arg(long, id = "MPC_PATH")]
+    mpc_table: Option<String>,
+
     /// AI level
     #[arg(short, long, default_value_t = DEFAULT_LEVEL)]
     level: u8,
 
+    /// Number of search threads
+    #[arg(short, long, default_value_t = 1)]
+    threads: usize,
+
+    /// Transposition table size in MB (rounded down to a power of two entries)
+    #[arg(long, id = "MB", default_value_t = 96)]
+    hash: usize,
+
+    /// Transposition table snapshot to load before and save after solving
+    #[arg(long, id = "TT_PATH")]
+    tt_file: Option<String>,
+
+    /// Solve only win/loss/draw in the endgame (with --solve)
+    #[arg(long)]
+    wld: bool,
+
+    /// Print search progress (each depth and aspiration re-search)
+    #[arg(short, long)]
+    verbose: bool,
+
     /// Number of self-play games to run
     /// (e.g. --self-play 10 --level 16 --self-play-out "./self-play.txt" --self-play-start-rand 45)
     #[arg(long, id = "Number of games")]
@@ -41,6 +78,15 @@ struct Args {
     #[arg(long, default_value_t = 20)]
     self_play_start_rand: usize,
 
+    /// Bitboard backend: "portable" or "avx2" (default: detected from the CPU)
+    #[arg(long)]
+    backend: Option<String>,
+
+    /// Convert the evaluator given by --eval-path and write it to this path
+    /// (JSON if the path ends with ".json", the compact binary format otherwise)
+    #[arg(long, id = "OUT_PATH")]
+    convert_eval: Option<String>,
+
     #[arg(long, id = "DEPTH")]
     perft: Option<u64>,
 
@@ -51,32 +97,93 @@ struct Args {
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
 
+    match &args.backend {
+        Some(backend) => set_backend(backend.parse()?)?,
+        None => init_backend(),
+    }
+
     let level = args.level as i32;
-    let eval_path = args.eval_path.as_deref().unwrap_or("../data/eval/eval.json");
+
+    if let Some(depth) = &args.perft {
+        // Perft mode
+        // e.g. --perft 11
+        run_perft(*depth, args.perft_count_pass);
+        return Ok(());
+    }
+
+    let evaluator = load_evaluator(&args)?;
+    let mpc_table = load_mpc_table(&args)?;
 
     if let Some(n_games) = args.self_play {
         // 自己対戦モード
-        let start_rand = args.self_play_start_rand;
-        let out_path = args.self_play_out;
-        run_self_play(n_games, level, start_rand, eval_path, &out_path)?;
+        let config = SelfPlayConfig {
+            n_games,
+            level,
+            n_threads: args.threads,
+            hash_mb: args.hash,
+            start_rand: args.self_play_start_rand,
+            mpc_table,
+            out_path: &args.self_play_out,
+        };
+        run_self_play(evaluator, &config)?;
     } else if let Some(path) = &args.solve {
         // Solveモード
         // e.g. -solve ".\problem\fforum-40-59.obf" -l 25
         println!("AI level   :  {}", args.level);
-        solve(path, eval_path, level);
-    } else if let Some(depth) = &args.perft {
-        // Perft mode
-        // e.g. --perft 11
-        run_perft(*depth, args.perft_count_pass);
-    }else {
+        println!("Threads    :  {}", args.threads);
+        println!("Backend    :  {}", current_backend());
+        let config = SolveConfig {
+            level,
+            n_threads: args.threads,
+            hash_mb: args.hash,
+            t_table_path: args.tt_file.as_deref(),
+            verbose: args.verbose,
+            wld: args.wld,
+            mpc_table,
+        };
+        solve(path, evaluator, &config);
+    } else if let Some(out_path) = &args.convert_eval {
+        // 評価関数の形式を変換する
+        // e.g. -e ../data/eval/eval.json --convert-eval ../data/eval/eval.bin
+        convert_eval(&evaluator, out_path)?;
+    } else {
         // 通常プレイモード
         let mut game = OthelloCLI::new(
             level,
-            eval_path
-        );
+            args.threads,
+            args.hash,
+            evaluator,
+            mpc_table,
+            args.verbose
+        )?;
         game.play();
     }
 
     Ok(())
 }
 
+
+/// 評価関数を読み込む
+///
+/// 全て 0 の評価関数は `--zero-eval` を指定した場合のみ使う (読み込みに失敗しても代わりには使わない)。
+fn load_evaluator(args: &Args) -> Result<Evaluator, Box<dyn std::error::Error>> {
+    if args.zero_eval {
+        return Ok(Evaluator::zero());
+    }
+    let eval_path = match &args.eval_path {
+        Some(path) => path.as_str(),
+        #[cfg(feature = "builtin-eval")]
+        None => return Ok(Evaluator::builtin()),
+        #[cfg(not(feature = "builtin-eval"))]
+        None => DEFAULT_EVAL_PATH,
+    };
+    Evaluator::read_file(eval_path).map_err(|e| format!("{}: {}", eval_path, e).into())
+}
+
+/// MPC のパラメータ表を読み込む (`--mpc-table` を省略した場合はコンパイル時の定数)
+fn load_mpc_table(args: &Args) -> Result<Arc<MpcTable>, Box<dyn std::error::Error>> {
+    match &args.mpc_table {
+        Some(path) => Ok(Arc::new(MpcTable::read_file(path).map_err(|e| e.to_string())?)),
+        None => Ok(Arc::new(MpcTable::default())),
+    }
+}
@@ -1,7 +1,10 @@
 use deft_reversi_engine::*;
+use deft_reversi_engine::evaluator_const::{N_PATTERN, SCORE_RATE};
+use crate::solve::print_progress;
 use std::{
     io::{self, Write},
     process::exit,
+    sync::Arc,
 };
 pub struct OthelloCLI {
     game: Game,
@@ -30,23 +33,97 @@ impl SettingTurn {
 }
 
 impl OthelloCLI {
-    pub fn new(ai_level: i32, eval_path: &str) -> Self {
-        let eval = match Evaluator::read_file(eval_path) {
-            Ok(e) => e,
-            Err(e) => {
-                eprintln!("Evaluator: {}", e);
-                Evaluator::default()
-            }
-        };
+    pub fn new(ai_level: i32, n_threads: usize, hash_mb: usize, evaluator: Evaluator, mpc_table: Arc<MpcTable>, verbose: bool) -> Result<Self, &'static str> {
+        let mut solver = Solver::with_t_table_size_mb(evaluator, hash_mb);
+        solver.set_n_threads(n_threads);
+        solver.set_mpc_table(mpc_table)?;
+        if verbose {
+            solver.set_observer(print_progress);
+        }
 
-        OthelloCLI {
+        Ok(OthelloCLI {
             game: Game::new(),
-            solver: Solver::new(eval),
+            solver,
             ai_level,
             setting_turn: SettingTurn {
                 black: Turn::Player,
                 white: Turn::Player,
             },
+        })
+    }
+
+    /// 盤面を1行ずつの文字列にする (列の見出し + 8行)
+    fn board_lines(&self) -> Vec<String> {
+        let mut lines = vec!["  A B C D E F G H".to_string()];
+        for i in 0..8 {
+            let mut line = format!("{}", i + 1);
+            for j in 0..8 {
+                line.push(' ');
+                let mask = 1u64 << (i * 8 + j);
+                if self.game.current.board.player & mask != 0 {
+                    line.push(self.game.current.turn.get_char());
+                } else if self.game.current.board.opponent & mask != 0 {
+                    line.push(self.game.current.turn.opponent().get_char());
+                } else {
+                    line.push('.');
+                }
+            }
+            lines.push(line);
+        }
+        lines
+    }
+
+    /// 評価関数による現在の局面の評価値の内訳を、盤面の横に表示する
+    fn display_eval_breakdown(&self) {
+        let Some(evaluator) = self.solver.search.eval_func.as_pattern_evaluator() else {
+            println!("The evaluator in use has no pattern breakdown.");
+            return;
+        };
+        let breakdown = evaluator.breakdown(&self.game.current.board);
+
+        let mut lines = vec![
+            format!(
+                "{} to move (table: parity {}, phase {})",
+                self.game.current.turn.get_str(),
+                breakdown.parity,
+                breakdown.phase
+            ),
+            format!(
+                "{:>7} | {:>12} {:>12} {:>12} {:>12} | {:>6}",
+                "pattern", "rotation 0", "rotation 1", "rotation 2", "rotation 3", "sum"
+            ),
+        ];
+        for pattern in 0..N_PATTERN {
+            let terms: Vec<String> = breakdown
+                .patterns
+                .iter()
+                .filter(|t| t.pattern == pattern)
+                .map(|t| format!("{:>5}:{:>+6}", t.feature, t.weight))
+                .collect();
+            lines.push(format!(
+                "{:>7} | {} | {:>+6}",
+                pattern,
+                terms.join(" "),
+                breakdown.pattern_total(pattern)
+            ));
+        }
+        lines.push(format!(
+            "mobility {:+} : {:+}, const : {:+}",
+            breakdown.mobility, breakdown.mobility_weight, breakdown.const_eval
+        ));
+        lines.push(format!(
+            "total {:+} (/ {} = {:+} discs)",
+            breakdown.total(),
+            SCORE_RATE,
+            breakdown.score()
+        ));
+
+        let board_lines = self.board_lines();
+        println!();
+        for i in 0..board_lines.len().max(lines.len()) {
+            let board_line = board_lines.get(i).map_or("", |l| l.as_str());
+            let eval_line = lines.get(i).map_or("", |l| l.as_str());
+            println!("{:<20}{}", board_line, eval_line);
         }
     }
 
@@ -63,21 +140,8 @@ impl OthelloCLI {
                 ),
             }
         };
-        println!("  A B C D E F G H");
-        for i in 0..8 {
-            print!("{}", i + 1);
-            for j in 0..8 {
-                print!(" ");
-                let mask = 1u64 << (i * 8 + j);
-                if self.game.current.board.player & mask != 0 {
-                    print!("{}", self.game.current.turn.get_char());
-                } else if self.game.current.board.opponent & mask != 0 {
-                    print!("{}", self.game.current.turn.opponent().get_char());
-                } else {
-                    print!(".");
-                }
-            }
-            println!();
+        for line in self.board_lines() {
+            println!("{}", line);
         }
 
         println!("-----------------------------------------------------------");
@@ -242,6 +306,9 @@ impl OthelloCLI {
                     }
                     break;
                 }
+                "eval" => {
+                    self.display_eval_breakdown();
+                }
                 "go" => {
                     self.computer_turn();
                     break;
@@ -266,7 +333,7 @@ impl OthelloCLI {
     fn computer_turn(&mut self) {
         let result = self.solver.solve(&self.game.current.board, self.ai_level);
         if let Ok(move_str) = position_bit_to_str(result.best_move) {
-            println!("move: {}", move_str);
+            println!("move: {} ({:+}, pv: {})", move_str, result.eval, result.pv_string());
             self.game.put(&move_str).unwrap();
         }
     }
@@ -307,6 +374,7 @@ impl OthelloCLI {
         println!("                                2 : Computer vs Computer");
         println!("                                3 : Player vs Player");
         println!("  go                        - Let the computer make a move.");
+        println!("  eval                      - Show how the evaluation function scores the position.");
         println!("  help                      - Show this help message.");
         println!("  quit | exit               - Exit the game.");
         println!();
@@ -3,33 +3,40 @@ use deft_reversi_engine::*;
 use std::fs::OpenOptions;
 use rand::prelude::*;
 use std::io::Write;
-
+use std::sync::Arc;
+
+
+/// 自己対戦の設定
+pub struct SelfPlayConfig<'a> {
+    pub n_games: usize,
+    pub level: i32,
+    pub n_threads: usize,
+    pub hash_mb: usize,
+    /// 各対局の最初にランダムに打つ手数
+    pub start_rand: usize,
+    pub mpc_table: Arc<MpcTable>,
+    /// 棋譜の保存先
+    pub out_path: &'a str,
+}
 
 /// 自己対戦を実行し、棋譜をファイルに保存する関数
-pub fn run_self_play(n_games: usize, level: i32, start_rand: usize, eval_path: &str, out_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+pub fn run_self_play(evaluator: Evaluator, config: &SelfPlayConfig) -> Result<(), Box<dyn std::error::Error>> {
     let mut rng = thread_rng();
     let mut file = OpenOptions::new()
         .create(true)
         .write(true)
         .truncate(true)  // ファイルを上書き
-        .open(out_path)?;
-
-    // Evaluatorを一度だけ読み込む
-    let evaluator = match Evaluator::read_file(eval_path) {
-        Ok(e) => e,
-        Err(e) => {
-            eprintln!("Evaluatorの読み込みに失敗しました（{}）。正しい評価を計算できません。", e);
-            Evaluator::default()
-        }
-    };
+        .open(config.out_path)?;
 
-    let mut solver = Solver::new(evaluator);
+    let mut solver = Solver::with_t_table_size_mb(evaluator, config.hash_mb);
+    solver.set_n_threads(config.n_threads);
+    solver.set_mpc_table(Arc::clone(&config.mpc_table))?;
 
-    for game_num in 1..=n_games {
+    for game_num in 1..=config.n_games {
         let mut game = Game::new();
 
         // 最初のstart_rand手をランダムに打つ
-        for _ in 0..start_rand {
+        for _ in 0..config.start_rand {
             let legal_moves = game.current.board.moves();
             if legal_moves == 0 {
                 if game.current.board.opponent_moves() != 0 {
@@ -66,7 +73,7 @@ pub fn run_self_play(n_games: usize, level: i32, start_rand: usize, eval_path: &
                 game.pass();
                 continue;
             }
-            let solver_result = solver.solve(&game.current.board, level);
+            let solver_result = solver.solve(&game.current.board, config.level);
 
             if solver_result.best_move == 0 {
                 #[cfg(debug_assertions)]
@@ -100,12 +107,10 @@ pub fn run_self_play(n_games: usize, level: i32, start_rand: usize, eval_path: &
         let record = game.record();
         writeln!(file, "{}", record)?;
 
-        solver.search.t_table.set_old();
-
         // 進捗表示（オプション）
-        println!("{} / {} ゲーム完了", game_num, n_games);
+        println!("{} / {} ゲーム完了", game_num, config.n_games);
     }
 
-    println!("自己対戦完了。\n棋譜は {} に保存されました。", out_path);
+    println!("自己対戦完了。\n棋譜は {} に保存されました。", config.out_path);
     Ok(())
 }
@@ -2,6 +2,7 @@ use std::time;
 
 use std::fs::File;
 use std::io::{self, BufRead};
+use std::sync::Arc;
 
 use deft_reversi_engine::*;
 
@@ -15,9 +16,61 @@ fn format_duration(duration: time::Duration) -> String {
     format!("{:02}:{:02}:{:02}.{:03}", hours, minutes, seconds, millis)
 }
 
-pub fn solve(path: &str, eval_path: &str, level: i32) {
-    let evaluator = Evaluator::read_file(eval_path).unwrap();
-    let mut solver = Solver::new(evaluator);
+/// 探索の途中経過を1行で表示する
+pub fn print_progress(progress: &SearchProgress) {
+    let score = match progress.bound {
+        ScoreBound::Exact => format!("{:+}", progress.score),
+        ScoreBound::Upper => format!("<={:+}", progress.score),
+        ScoreBound::Lower => format!(">={:+}", progress.score),
+    };
+    println!(
+        "  {:28} [{:+3}, {:+3}]  {:>6}  {:>4}  {:>12} nodes  {}",
+        progress.solver_type.description(),
+        progress.alpha,
+        progress.beta,
+        score,
+        position_bit_to_str(progress.best_move).unwrap(),
+        progress.searched_nodes,
+        format_duration(progress.elapsed)
+    );
+}
+
+/// Solveモードの設定
+pub struct SolveConfig<'a> {
+    pub level: i32,
+    pub n_threads: usize,
+    pub hash_mb: usize,
+    /// 置換表のスナップショットのパス (探索前に読み込み、探索後に保存する)
+    pub t_table_path: Option<&'a str>,
+    /// 探索の途中経過を表示する
+    pub verbose: bool,
+    /// 勝敗のみを求める
+    pub wld: bool,
+    pub mpc_table: Arc<MpcTable>,
+}
+
+pub fn solve(path: &str, evaluator: Evaluator, config: &SolveConfig) {
+    let mut solver = Solver::with_t_table_size_mb(evaluator, config.hash_mb);
+    solver.set_n_threads(config.n_threads);
+    if let Err(e) = solver.set_mpc_table(Arc::clone(&config.mpc_table)) {
+        eprintln!("{}", e);
+        return;
+    }
+    println!(
+        "Hash       :  {:.1} MB ({} entries)",
+        solver.search.t_table.memory_size() as f64 / (1 << 20) as f64,
+        solver.search.t_table.n_entries()
+    );
+    if let Some(t_table_path) = config.t_table_path {
+        match solver.load_t_table(t_table_path) {
+            Ok(n) => println!("Hash file  :  {} entries loaded from {}", n, t_table_path),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => eprintln!("Hash file  :  {} is not loaded ({})", t_table_path, e),
+        }
+    }
+    if config.verbose {
+        solver.set_observer(print_progress);
+    }
 
     let board_list: Vec<Board> = match read_solve_file(path) {
         Ok(b) => b,
@@ -31,8 +84,8 @@ pub fn solve(path: &str, eval_path: &str, level: i32) {
     let mut total_nodes = 0;
 
     let table_header = format!(
-        "{:5} | {:5} | {:4} | {:9} | {:28} | {:12} | {:15} | {:14}",
-        " #", "score", "move", "n_empties", "solver", "node", "nps", "time"
+        "{:5} | {:5} | {:4} | {:9} | {:28} | {:12} | {:15} | {:14} | {}",
+        " #", "score", "move", "n_empties", "solver", "node", "nps", "time", "pv"
     );
     let table_separator_line = "-".repeat(table_header.len());
 
@@ -41,13 +94,25 @@ pub fn solve(path: &str, eval_path: &str, level: i32) {
     println!("{}", table_separator_line);
     for (i, board) in board_list.iter().enumerate() {
         let solve_start_time = time::Instant::now();
-        let solver_result = solver.solve(board, level);
+        let solver_result = if config.wld {
+            solver.solve_wld(board, config.level)
+        } else {
+            solver.solve(board, config.level)
+        };
+        let score = match solver_result.solver_type {
+            SolverType::Wld(_) => match solver_result.eval {
+                1.. => "win".to_string(),
+                0 => "draw".to_string(),
+                _ => "loss".to_string(),
+            },
+            _ => format!("{:+}", solver_result.eval),
+        };
         let solve_time = solve_start_time.elapsed();
 
         println!(
-            "{:>5}   {:+5}   {:>4}   {:>9}   {:28}   {:>12}   {:>15.3}   {:>14} ",
+            "{:>5}   {:>5}   {:>4}   {:>9}   {:28}   {:>12}   {:>15.3}   {:>14}   {}",
             i + 1,
-            solver_result.eval,
+            score,
             position_bit_to_str(solver_result.best_move).unwrap(),
             board.empties_count(),
             solver_result
@@ -55,7 +120,8 @@ pub fn solve(path: &str, eval_path: &str, level: i32) {
                 .description(),
             solver_result.searched_nodes,
             solver_result.searched_nodes as f64 / solve_time.as_secs_f64(),
-            format_duration(solve_time)
+            format_duration(solve_time),
+            solver_result.pv_string()
         );
         total_nodes += solver_result.searched_nodes;
     }
@@ -76,6 +142,13 @@ pub fn solve(path: &str, eval_path: &str, level: i32) {
     );
 
     println!("{}", table_separator_line);
+    println!("Hash usage :  {:.1}%", solver.search.t_table.fill_rate() * 100.0);
+
+    if let Some(t_table_path) = config.t_table_path {
+        if let Err(e) = solver.save_t_table(t_table_path) {
+            eprintln!("Hash file  :  failed to save {} ({})", t_table_path, e);
+        }
+    }
 }
 
 fn read_solve_file(path: &str) -> Result<Vec<Board>, std::io::Error> {
@@ -0,0 +1,42 @@
+// `builtin-eval` feature で埋め込む評価関数 (data/eval/eval.bin) があるか確認する
+//
+// 環境変数 `DEFT_REVERSI_EVAL_BIN` でファイルのパスを指定すると、data/eval/eval.bin の代わりに埋め込む。
+// ファイルが無い場合は `builtin_eval_missing` を、バイナリ形式でない場合は `builtin_eval_not_binary` を設定し、
+// eval.rs の `compile_error!` で用意の仕方を示す。
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// 評価関数のバイナリ形式の識別子 (eval_io.rs の `BINARY_MAGIC`)
+const BINARY_MAGIC: [u8; 4] = *b"DREV";
+
+fn main() {
+    println!("cargo:rustc-check-cfg=cfg(builtin_eval_missing)");
+    println!("cargo:rustc-check-cfg=cfg(builtin_eval_not_binary)");
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=DEFT_REVERSI_EVAL_BIN");
+    if env::var_os("CARGO_FEATURE_BUILTIN_EVAL").is_none() {
+        return;
+    }
+
+    let path = match env::var_os("DEFT_REVERSI_EVAL_BIN") {
+        Some(path) => PathBuf::from(path),
+        None => Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("../data/eval/eval.bin"),
+    };
+    println!("cargo:rerun-if-changed={}", path.display());
+    if !path.is_file() {
+        println!("cargo:rustc-cfg=builtin_eval_missing");
+        return;
+    }
+
+    // JSON 形式のまま置かれた場合は、実行時ではなくビルド時に気付けるようにする
+    let mut magic = [0; 4];
+    let is_binary = File::open(&path).and_then(|mut f| f.read_exact(&mut magic)).is_ok() && magic == BINARY_MAGIC;
+    if !is_binary {
+        println!("cargo:rustc-cfg=builtin_eval_not_binary");
+        return;
+    }
+    println!("cargo:rustc-env=DEFT_REVERSI_BUILTIN_EVAL_PATH={}", path.canonicalize().unwrap().display());
+}
@@ -1,4 +1,6 @@
-use crate::{bit::*, flip, get_moves::get_moves};
+use crate::{bit::*, flip, get_moves::get_moves, stability::get_stable_discs};
+#[cfg(target_arch = "x86_64")]
+use crate::cpu::use_avx2;
 
 pub const A1: u8 = 0;
 pub const B1: u8 = 1;
@@ -74,6 +76,7 @@ pub struct Board {
     pub opponent: u64
 }
 
+#[derive(Debug)]
 pub enum PutPieceErr {
     NoValidPlacement,
     Unknown(String)
@@ -128,12 +131,11 @@ impl Board {
         let p: u64 = self.player;
         let o: u64 = self.opponent;
         
-        #[cfg(target_feature = "avx2")]
-        unsafe {
-            flip::flip_avx2(x.trailing_zeros() as usize, p, o)
+        #[cfg(target_arch = "x86_64")]
+        if use_avx2() {
+            return unsafe { flip::flip_avx2(x.trailing_zeros() as usize, p, o) };
         }
-        
-        #[cfg(not(target_feature = "avx2"))]
+
         flip::flip_std(x, p, o)
     }
 
@@ -170,6 +172,18 @@ impl Board {
         get_moves(p, o)
     }
 
+    /// 手番側の確定石 (の一部)
+    #[inline(always)]
+    pub fn stable_discs(&self) -> u64 {
+        get_stable_discs(self.player, self.opponent)
+    }
+
+    /// 相手側の確定石 (の一部)
+    #[inline(always)]
+    pub fn opponent_stable_discs(&self) -> u64 {
+        get_stable_discs(self.opponent, self.player)
+    }
+
     pub fn all_symmetries(&self) -> Vec<Board>
     {
         let mut symmetries = Vec::new();
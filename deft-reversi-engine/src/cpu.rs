@@ -0,0 +1,181 @@
+// CPU features and runtime dispatch of the bitboard backend
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// 実行中のCPUが対応している命令セット
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CpuFeatures {
+    pub avx2: bool,
+    pub bmi2: bool,
+}
+
+impl CpuFeatures {
+    /// 実行時にCPUの対応命令を調べる
+    pub fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            Self {
+                avx2: is_x86_feature_detected!("avx2"),
+                bmi2: is_x86_feature_detected!("bmi2"),
+            }
+        }
+
+        #[cfg(not(target_arch = "x86_64"))]
+        Self { avx2: false, bmi2: false }
+    }
+}
+
+/// 着手可能位置の計算・石の反転に用いる実装
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// どのCPUでも動作する実装
+    Portable,
+    /// AVX2 を用いた実装
+    Avx2,
+}
+
+impl Backend {
+    /// 実行中のCPUで使える最も速い実装
+    pub fn detect() -> Self {
+        if Backend::Avx2.is_supported() {
+            Backend::Avx2
+        } else {
+            Backend::Portable
+        }
+    }
+
+    /// 実行中のCPUでこの実装が使えるか
+    pub fn is_supported(self) -> bool {
+        match self {
+            Backend::Portable => true,
+            Backend::Avx2 => CpuFeatures::detect().avx2,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Portable => "portable",
+            Backend::Avx2 => "avx2",
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "portable" => Ok(Backend::Portable),
+            "avx2" => Ok(Backend::Avx2),
+            _ => Err(format!("unknown backend: {} (expected \"portable\" or \"avx2\")", s)),
+        }
+    }
+}
+
+/// 実行中のCPUが対応していない実装を指定した
+#[derive(Debug)]
+pub struct UnsupportedBackend(pub Backend);
+
+impl fmt::Display for UnsupportedBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "backend \"{}\" is not supported by this CPU", self.0)
+    }
+}
+
+impl std::error::Error for UnsupportedBackend {}
+
+const BACKEND_UNINIT: u8 = 0;
+const BACKEND_PORTABLE: u8 = 1;
+const BACKEND_AVX2: u8 = 2;
+
+/// 選択されている実装
+///
+/// 初期化前は `Portable` として扱うため、どのCPUでも安全に動作する。
+static BACKEND: AtomicU8 = AtomicU8::new(BACKEND_UNINIT);
+
+/// AVX2 の実装が選択されているか (探索中に頻繁に呼ばれる)
+#[inline(always)]
+pub(crate) fn use_avx2() -> bool {
+    BACKEND.load(Ordering::Relaxed) == BACKEND_AVX2
+}
+
+/// CPUを調べて実装を選択する
+///
+/// 既に選択されている場合 (`set_backend` で指定した場合を含む) は何もしない。
+/// `SearchEngine` の生成時に呼ばれる。
+pub fn init_backend() {
+    if BACKEND.load(Ordering::Relaxed) == BACKEND_UNINIT {
+        let _ = BACKEND.compare_exchange(
+            BACKEND_UNINIT,
+            backend_to_u8(Backend::detect()),
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        );
+    }
+}
+
+/// 実装を指定する (ベンチマーク用)
+///
+/// 実行中のCPUが対応していない実装は指定できない。
+pub fn set_backend(backend: Backend) -> Result<(), UnsupportedBackend> {
+    if !backend.is_supported() {
+        return Err(UnsupportedBackend(backend));
+    }
+    BACKEND.store(backend_to_u8(backend), Ordering::Relaxed);
+    Ok(())
+}
+
+/// 現在選択されている実装
+pub fn current_backend() -> Backend {
+    if use_avx2() {
+        Backend::Avx2
+    } else {
+        Backend::Portable
+    }
+}
+
+fn backend_to_u8(backend: Backend) -> u8 {
+    match backend {
+        Backend::Portable => BACKEND_PORTABLE,
+        Backend::Avx2 => BACKEND_AVX2,
+    }
+}
+
+#[cfg(all(test, target_arch = "x86_64"))]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::eval_simple::put_random_piece;
+    use crate::flip::{flip_avx2, flip_std};
+    use crate::get_moves::{get_moves_avx2, get_moves_std};
+
+    #[test]
+    fn backends_agree() {
+        if !Backend::Avx2.is_supported() {
+            return;
+        }
+        for _ in 0..100 {
+            let mut board = Board::new();
+            while board.moves() != 0 {
+                let (p, o) = (board.player, board.opponent);
+                let moves = get_moves_std(p, o);
+                assert_eq!(unsafe { get_moves_avx2(p, o) }, moves);
+                for x in 0..64 {
+                    if moves & (1 << x) != 0 {
+                        let flip = unsafe { flip_avx2(x, p, o) };
+                        assert_eq!(flip, flip_std(1 << x, p, o));
+                    }
+                }
+                put_random_piece(&mut board).unwrap();
+            }
+        }
+    }
+}
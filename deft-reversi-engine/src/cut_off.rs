@@ -10,7 +10,7 @@ pub fn t_table_cut_off_td(
     beta: &mut i32,
     lv: i32,
     selectivity_lv: i32,
-    table_data: &Option<&TableData>,
+    table_data: &Option<TableData>,
 ) -> Option<i32> {
     if let Some(t) = table_data {
         if t.lv as i32 != lv || t.selectivity_lv as i32 != selectivity_lv {
@@ -114,4 +114,32 @@ pub fn et_cut_off(
         }
     }
     None
-}
\ No newline at end of file
+}
+
+/// 確定石による枝刈り
+///
+/// 相手の確定石から最終スコアの上限を、自分の確定石から下限を求める。
+/// 探索窓の外側であれば、その値を返す。そうでなければ、探索窓を狭める。
+#[inline(always)]
+pub fn stability_cut_off(board: &Board, alpha: &mut i32, beta: &mut i32) -> Option<i32> {
+    // 石数から見て、窓の外に出る可能性がある場合のみ確定石を数える
+    if 64 - 2 * board.opponent.count_ones() as i32 <= *alpha {
+        let max = 64 - 2 * board.opponent_stable_discs().count_ones() as i32;
+        if max <= *alpha {
+            return Some(max);
+        }
+        if max < *beta {
+            *beta = max;
+        }
+    }
+    if 2 * board.player.count_ones() as i32 - 64 >= *beta {
+        let min = 2 * board.stable_discs().count_ones() as i32 - 64;
+        if min >= *beta {
+            return Some(min);
+        }
+        if min > *alpha {
+            *alpha = min;
+        }
+    }
+    None
+}
@@ -146,13 +146,11 @@ X.......
 -------------------------
 */
 
-use std::fs::File;
-use std::fs;
 use serde::{Deserialize, Serialize};
-use std::io::*;
 
 
 use crate::board::*;
+use crate::eval_feature::*;
 
 pub mod evaluator_const {
     use crate::board::*;
@@ -316,14 +314,45 @@ pub struct EvaluationScores {
     pub const_eval: i16
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Evaluator {
     pub version: String,
     pub n_deta_set: i32,
     pub n_iteration: i32,
     pub eval: Vec<Vec<EvaluationScores>>,
-    #[serde(skip)]
-    pub feature_bit: [[u16; N_ROTATION]; N_PATTERN],
+}
+
+/// 評価のたびに特徴を計算するための作業領域
+///
+/// 評価関数の重み (`Evaluator`) は読み込み後に変更しないため `Arc` で共有し、
+/// この作業領域だけを探索エンジンごとに持つ。
+#[derive(Clone, Default)]
+pub struct FeatureScratch {
+    pub feature_bit: FeatureBit,
+    /// 特徴の計算方法 (既定では実行中のCPUに合わせて選択される)
+    feature_extractor: FeatureExtractor,
+}
+
+impl FeatureScratch {
+    /// 実行中のCPUで使えない方法を指定した場合は、`Table` を用いる
+    pub fn new(feature_extractor: FeatureExtractor) -> Self {
+        Self {
+            feature_bit: [[0; N_ROTATION]; N_PATTERN],
+            feature_extractor: feature_extractor.or_supported(),
+        }
+    }
+
+    /// 特徴の計算方法
+    #[inline(always)]
+    pub fn feature_extractor(&self) -> FeatureExtractor {
+        self.feature_extractor
+    }
+
+    #[inline(always)]
+    pub fn clac_features(&mut self, board: &Board)
+    {
+        self.feature_bit = calc_features(board.player, board.opponent, self.feature_extractor);
+    }
 }
 
 
@@ -349,71 +378,99 @@ impl Default for EvaluationScores {
     }
 }
 
-impl Default for Evaluator {
-    fn default() -> Self {        
+/// `builtin-eval` feature を有効にしたときに埋め込む評価関数 (`eval_io` のバイナリ形式)
+///
+/// 既定では data/eval/eval.bin を、環境変数 `DEFT_REVERSI_EVAL_BIN` を指定した場合はそのファイルを埋め込む。
+#[cfg(all(feature = "builtin-eval", not(builtin_eval_missing), not(builtin_eval_not_binary)))]
+static BUILTIN_EVAL: &[u8] = include_bytes!(env!("DEFT_REVERSI_BUILTIN_EVAL_PATH"));
+
+// data/eval/eval.bin はリポジトリに含まれないため、ビルドの前に用意する (build.rs で確認する)
+#[cfg(all(feature = "builtin-eval", builtin_eval_missing))]
+compile_error!(
+    "the builtin-eval feature needs data/eval/eval.bin (or the file given by DEFT_REVERSI_EVAL_BIN), \
+     which is not in the repository. \
+     Create it from an evaluator with `cargo run --release -p deft-reversi-cli -- -e <eval.json> --convert-eval data/eval/eval.bin` \
+     (or train one with `deft-reversi-learn train -o data/eval/eval.bin`), or build without the builtin-eval feature."
+);
+#[cfg(all(feature = "builtin-eval", builtin_eval_not_binary))]
+compile_error!(
+    "the evaluator embedded by the builtin-eval feature is not in the binary format. \
+     Convert it with `cargo run --release -p deft-reversi-cli -- -e <eval.json> --convert-eval data/eval/eval.bin`."
+);
+#[cfg(all(feature = "builtin-eval", any(builtin_eval_missing, builtin_eval_not_binary)))]
+static BUILTIN_EVAL: &[u8] = &[];
+
+impl Evaluator {
+    /// 全ての評価値が 0 の評価関数
+    ///
+    /// 常に 0 と評価するため、対局には使えない (探索のベンチマークやテスト用)。
+    pub fn zero() -> Self {
         Self{
             version: "0".to_string(),
             n_deta_set: 0,
             n_iteration: 0,
             eval: vec![vec![EvaluationScores::default();N_PHASE]; 2],
-            feature_bit: [[0; N_ROTATION]; N_PATTERN],
         } 
     }
-}
-
 
-
-impl Evaluator {
-    pub fn new() -> Self
-    {
-        Self::default()
+    /// ビルド時に埋め込んだ評価関数 (`data/eval/eval.bin` または `DEFT_REVERSI_EVAL_BIN` で指定したファイル)
+    ///
+    /// 埋め込んだデータはビルド時に用意したものなので、読み込みに失敗した場合は panic する。
+    #[cfg(feature = "builtin-eval")]
+    pub fn builtin() -> Self {
+        Self::from_binary(BUILTIN_EVAL).expect("the builtin evaluator is broken")
     }
 
-
+    /// 局面の評価に用いる評価値の表 `eval[parity][phase]` の添字 (parity, phase)
+    ///
+    /// 学習 (`deft-reversi-learn`) でも同じ表に振り分けるために公開している。
     #[inline(always)]
-    pub fn clac_features(&mut self, board: &Board)
-    {
-        self.feature_bit = [[0; N_ROTATION]; N_PATTERN];
-        
-        let p: u64 = board.player;
-        let o: u64 = board.opponent;
-        
-        for pattern in 0..N_PATTERN {
-            let fbit = &mut self.feature_bit[pattern];
-            for rotation in 0..N_ROTATION {
-                for coord_i in 0..FEATURE_COORD[pattern].n_square {
-                    let coord = FEATURE_COORD[pattern].feature_coord[rotation][coord_i as usize];
-
-                    #[cfg(debug_assertions)]
-                    if coord == NO_COORD {panic!()}
-
-                    let color = 2 * (1 & p >> coord) + (1 & o >> coord);
-                    fbit[rotation] = fbit[rotation] * 3u16 + color as u16;
-                }
+    pub fn table_index(board: &Board) -> (usize, usize) {
+        (board.empties_count() as usize % 2, board.move_count() as usize / 2)
+    }
+
+    /// 評価値の内訳 (各パターンの各向き、着手可能数の差、定数項の寄与)
+    ///
+    /// 対局の検討用で、探索では使わない。
+    pub fn breakdown(&self, board: &Board) -> EvalBreakdown {
+        let (parity, phase) = Self::table_index(board);
+        let eval_scores = &self.eval[parity][phase];
+        let feature_bit = calc_features(board.player, board.opponent, FeatureExtractor::default());
+
+        let mut patterns = Vec::with_capacity(N_FEAUTURE);
+        for (pattern, (e, f)) in eval_scores.pattern_eval.iter().zip(&feature_bit).enumerate() {
+            for (rotation, &feature) in f.iter().enumerate() {
+                patterns.push(PatternTerm { pattern, rotation, feature, weight: e[feature as usize] });
             }
         }
+
+        let mobility = board.moves().count_ones() as i32 - board.opponent_moves().count_ones() as i32;
+        EvalBreakdown {
+            parity,
+            phase,
+            patterns,
+            mobility,
+            mobility_weight: eval_scores.mobility_eval[(N_MOBILITY_BASE as i32 + mobility) as usize],
+            const_eval: eval_scores.const_eval,
+        }
     }
 
+    /// 計算済みの特徴 (`FeatureScratch`, `IncrementalFeatures` など) を用いて評価する
     #[inline(always)]
-    pub fn calc_eval(&self, board: &Board) -> i32
+    pub fn calc_eval_from_features(&self, board: &Board, feature_bit: &FeatureBit) -> i32
     {
-        let move_count = board.move_count();
-        let phase = move_count as usize / 2;
-
         let mut evaluation  = 0;
         
         // todo: eval の実装を見直す。
         // let eval_scores = &self.eval[board.next_turn][phase];
-        let eval_scores = &self.eval[board.empties_count() as usize % 2][phase];
-        for pattern in 0..N_PATTERN {
-            // let e = &eval_scores.pattern_eval[pattern];
-            // let f = &self.feature_bit[pattern];
-
+        let (parity, phase) = Self::table_index(board);
+        let eval_scores = &self.eval[parity][phase];
+        for (e, f) in eval_scores.pattern_eval.iter().zip(feature_bit) {
             // for each rotaion
-            evaluation += eval_scores.pattern_eval[pattern][self.feature_bit[pattern][0] as usize] as i32 
-                            + eval_scores.pattern_eval[pattern][self.feature_bit[pattern][1] as usize] as i32
-                            + eval_scores.pattern_eval[pattern][self.feature_bit[pattern][2] as usize] as i32
-                            + eval_scores.pattern_eval[pattern][self.feature_bit[pattern][3] as usize] as i32;
+            evaluation += e[f[0] as usize] as i32 
+                            + e[f[1] as usize] as i32
+                            + e[f[2] as usize] as i32
+                            + e[f[3] as usize] as i32;
         }
 
         let mobility = 
@@ -429,39 +486,105 @@ impl Evaluator {
 
 
     #[inline(always)]
-    pub fn clac_features_eval(&mut self, board: &Board) -> i32{
+    pub fn clac_features_eval(&self, board: &Board, scratch: &mut FeatureScratch) -> i32{
 
-        self.clac_features(board);
-        let mut e = self.calc_eval(board);
-
-        if e > 0 {e += SCORE_RATE/2;} else if e < 0 {e -= SCORE_RATE/2;}
-        e /= SCORE_RATE;
+        scratch.clac_features(board);
+        self.scaled_eval_from_features(board, &scratch.feature_bit)
+    }
 
-        if e > SCORE_MAX {e = SCORE_MAX;} else if e < -SCORE_MAX {e = -SCORE_MAX;}     
-        e
+    /// 計算済みの特徴を用いて評価し、石差 (`-SCORE_MAX..=SCORE_MAX`) に変換する
+    #[inline(always)]
+    pub fn scaled_eval_from_features(&self, board: &Board, feature_bit: &FeatureBit) -> i32 {
+        scale_eval(self.calc_eval_from_features(board, feature_bit))
     }
+}
 
-    pub fn write_file(&self, path: &str) -> std::io::Result<()>
-    {
-        // serialized
-        let serialized: String = serde_json::to_string(self).unwrap();
+/// 評価値 (`SCORE_RATE` 倍) を石差 (`-SCORE_MAX..=SCORE_MAX`) に変換する
+#[inline(always)]
+fn scale_eval(mut e: i32) -> i32 {
+    if e > 0 {e += SCORE_RATE/2;} else if e < 0 {e -= SCORE_RATE/2;}
+    e /= SCORE_RATE;
+
+    if e > SCORE_MAX {e = SCORE_MAX;} else if e < -SCORE_MAX {e = -SCORE_MAX;}     
+    e
+}
 
-        // write
-        let mut file = File::create(path)?;
-        file.write_all(serialized.as_bytes())?;
-        Ok(())
+/// パターンの1つの向きの、評価値への寄与
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PatternTerm {
+    /// パターン (`FEATURE_COORD` の添字)
+    pub pattern: usize,
+    /// パターンの向き (0..N_ROTATION)
+    pub rotation: usize,
+    /// 特徴 (パターン内の石の配置を3進数で表した値)
+    pub feature: u16,
+    /// 特徴に対応する評価値 (`SCORE_RATE` 倍)
+    pub weight: i16,
+}
+
+/// 評価値の内訳 (`Evaluator::breakdown`)
+#[derive(Clone, Debug)]
+pub struct EvalBreakdown {
+    /// 使用した評価値の表 `eval[parity][phase]`
+    pub parity: usize,
+    pub phase: usize,
+    /// 各パターンの各向きの寄与 (パターン, 向きの順)
+    pub patterns: Vec<PatternTerm>,
+    /// 着手可能数の差 (手番側 - 相手側)
+    pub mobility: i32,
+    pub mobility_weight: i16,
+    pub const_eval: i16,
+}
+
+impl EvalBreakdown {
+    /// パターン `pattern` の全ての向きの寄与の和
+    pub fn pattern_total(&self, pattern: usize) -> i32 {
+        self.patterns.iter().filter(|t| t.pattern == pattern).map(|t| t.weight as i32).sum()
     }
 
-    pub fn read_file(path : &str) -> std::io::Result<Evaluator>
-    {
-        let input = fs::read_to_string(path)?;
-        let deserialized: Evaluator = serde_json::from_str(&input).unwrap();
-        Ok(deserialized)
+    /// 評価値の合計 (`SCORE_RATE` 倍)
+    pub fn total(&self) -> i32 {
+        self.patterns.iter().map(|t| t.weight as i32).sum::<i32>()
+            + self.mobility_weight as i32
+            + self.const_eval as i32
     }
 
-    pub fn read_string(input: &str) -> std::io::Result<Evaluator>
-    {
-        let deserialized: Evaluator = serde_json::from_str(input).unwrap();
-        Ok(deserialized)
+    /// 石差に変換した評価値 (探索で用いる評価値と同じ)
+    pub fn score(&self) -> i32 {
+        scale_eval(self.total())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval_simple::put_random_piece;
+
+    #[test]
+    fn breakdown_adds_up_to_eval() {
+        let mut evaluator = Evaluator::zero();
+        let mut x = 1i16;
+        for scores in evaluator.eval.iter_mut().flatten() {
+            for e in scores.pattern_eval.iter_mut().flatten().chain(scores.mobility_eval.iter_mut()) {
+                x = x.wrapping_mul(75).wrapping_add(74) % 300;
+                *e = x;
+            }
+            scores.const_eval = x;
+        }
+
+        let mut scratch = FeatureScratch::default();
+        for _ in 0..20 {
+            let mut board = Board::new();
+            while board.moves() != 0 {
+                let breakdown = evaluator.breakdown(&board);
+                assert_eq!(breakdown.patterns.len(), N_FEAUTURE);
+                assert_eq!(breakdown.score(), evaluator.clac_features_eval(&board, &mut scratch));
+                assert_eq!(
+                    (0..N_PATTERN).map(|p| breakdown.pattern_total(p)).sum::<i32>(),
+                    breakdown.total() - breakdown.mobility_weight as i32 - breakdown.const_eval as i32
+                );
+                put_random_piece(&mut board).unwrap();
+            }
+        }
     }
 }
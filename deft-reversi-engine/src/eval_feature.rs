@@ -0,0 +1,488 @@
+// Pattern feature extraction with lookup tables / BMI2 PEXT
+// Optimization ideas :
+//    Egaroucid (Takuto Yamana)
+//    Egaroucid src          : https://github.com/Nyanyan/Egaroucid
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::_pext_u64;
+
+use crate::bit::transpose;
+use crate::board::NO_COORD;
+use crate::cpu::CpuFeatures;
+use crate::eval::evaluator_const::*;
+
+/// パターンの特徴 (3進数のインデックス)
+pub type FeatureBit = [[u16; N_ROTATION]; N_PATTERN];
+
+/// 評価関数の特徴の計算方法
+///
+/// どの方法でも、計算される特徴は同じである。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeatureExtractor {
+    /// パターンのマスを1つずつ調べる
+    Scalar,
+    /// 盤面の行ごとに、ビット列を3進数に変換するテーブルを引く
+    Table,
+    /// BMI2 の PEXT 命令でパターンのマスを取り出し、3進数に変換するテーブルを引く
+    ///
+    /// Zen2 以前の AMD のCPUでは PEXT が遅いため、`Table` の方が速い。
+    Pext,
+}
+
+impl FeatureExtractor {
+    /// 実行中のCPUで使える最も速い方法
+    pub fn detect() -> Self {
+        if FeatureExtractor::Pext.is_supported() {
+            FeatureExtractor::Pext
+        } else {
+            FeatureExtractor::Table
+        }
+    }
+
+    /// 実行中のCPUでこの方法が使えるか
+    pub fn is_supported(self) -> bool {
+        match self {
+            FeatureExtractor::Scalar | FeatureExtractor::Table => true,
+            FeatureExtractor::Pext => CpuFeatures::detect().bmi2,
+        }
+    }
+
+    /// 実行中のCPUで使えない方法なら `Table` に置き換える
+    ///
+    /// 特徴を計算するたびにCPUを調べずに済むよう、方法を選ぶとき (`FeatureScratch::new` など) に呼ぶ。
+    pub fn or_supported(self) -> Self {
+        if self.is_supported() {
+            self
+        } else {
+            FeatureExtractor::Table
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FeatureExtractor::Scalar => "scalar",
+            FeatureExtractor::Table => "table",
+            FeatureExtractor::Pext => "pext",
+        }
+    }
+}
+
+impl Default for FeatureExtractor {
+    fn default() -> Self {
+        Self::detect()
+    }
+}
+
+impl fmt::Display for FeatureExtractor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for FeatureExtractor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "scalar" => Ok(FeatureExtractor::Scalar),
+            "table" => Ok(FeatureExtractor::Table),
+            "pext" => Ok(FeatureExtractor::Pext),
+            _ => Err(format!(
+                "unknown feature extractor: {} (expected \"scalar\", \"table\" or \"pext\")",
+                s
+            )),
+        }
+    }
+}
+
+/// 盤面の1行 (または転置した盤面の1行) と、その行の変換テーブル
+#[derive(Clone, Copy)]
+struct LineTable {
+    /// 0..8: 盤面の行, 8..16: 転置した盤面の行
+    line: u8,
+    table: u16,
+}
+
+struct FeatureTables {
+    /// 各特徴のマス
+    masks: [[u64; N_ROTATION]; N_PATTERN],
+    /// `pext_ternary[pext_offset[..][..] + bits]`:
+    /// PEXT で取り出したビット列 (マス番号の昇順) を3進数に変換した値
+    pext_offset: [[usize; N_ROTATION]; N_PATTERN],
+    pext_ternary: Box<[u16]>,
+    /// 各特徴が含まれる行 (`lines[line_range[..][..]]`)
+    line_range: [[(usize, usize); N_ROTATION]; N_PATTERN],
+    lines: Box<[LineTable]>,
+    /// `line_ternary[table][byte]`: 行のビット列を3進数に変換した値
+    line_ternary: Box<[[u16; 256]]>,
+    /// `square_weights[x]`: 各特徴での、マス `x` の3進数での重み (含まれない特徴では 0)
+    square_weights: Box<[FeatureBit; 64]>,
+}
+
+static FEATURE_TABLES: OnceLock<FeatureTables> = OnceLock::new();
+
+fn feature_tables() -> &'static FeatureTables {
+    FEATURE_TABLES.get_or_init(|| {
+        let mut masks = [[0; N_ROTATION]; N_PATTERN];
+        let mut pext_offset = [[0; N_ROTATION]; N_PATTERN];
+        let mut pext_ternary = Vec::new();
+        let mut line_range = [[(0, 0); N_ROTATION]; N_PATTERN];
+        let mut lines = Vec::new();
+        let mut line_ternary = Vec::new();
+        let mut square_weights = Box::new([[[0; N_ROTATION]; N_PATTERN]; 64]);
+
+        for pattern in 0..N_PATTERN {
+            let n_square = FEATURE_COORD[pattern].n_square as usize;
+            for rotation in 0..N_ROTATION {
+                // マス番号 -> そのマスの3進数での重み
+                let mut weights = [0u16; 64];
+                let mut mask = 0u64;
+                for (i, &coord) in FEATURE_COORD[pattern].feature_coord[rotation][..n_square].iter().enumerate() {
+                    weights[coord as usize] = P3[n_square - 1 - i] as u16;
+                    mask |= 1 << coord;
+                    square_weights[coord as usize][pattern][rotation] = P3[n_square - 1 - i] as u16;
+                }
+                masks[pattern][rotation] = mask;
+
+                // PEXT のビット j は、パターンのマスのうち j 番目に小さいマス
+                let squares: Vec<usize> = (0..64).filter(|&x| mask & (1 << x) != 0).collect();
+                pext_offset[pattern][rotation] = pext_ternary.len();
+                for bits in 0..(1usize << n_square) {
+                    let value = squares
+                        .iter()
+                        .enumerate()
+                        .filter(|&(j, _)| bits & (1 << j) != 0)
+                        .map(|(_, &x)| weights[x])
+                        .sum();
+                    pext_ternary.push(value);
+                }
+
+                // 含まれる行が少ない方 (そのままか転置か) を使う
+                let rows = (0..8).filter(|r| mask & (0xFF << (8 * r)) != 0).count();
+                let transposed = (0..8).filter(|r| transpose(mask) & (0xFF << (8 * r)) != 0).count() < rows;
+                let start = lines.len();
+                for row in 0..8 {
+                    let square = |b: usize| if transposed { 8 * b + row } else { 8 * row + b };
+                    if (0..8).all(|b| mask & (1 << square(b)) == 0) {
+                        continue;
+                    }
+                    lines.push(LineTable {
+                        line: (row + 8 * transposed as usize) as u8,
+                        table: line_ternary.len() as u16,
+                    });
+                    let mut table = [0u16; 256];
+                    for (byte, value) in table.iter_mut().enumerate() {
+                        *value = (0..8)
+                            .filter(|&b| byte & (1 << b) != 0)
+                            .map(|b| weights[square(b)])
+                            .sum();
+                    }
+                    line_ternary.push(table);
+                }
+                line_range[pattern][rotation] = (start, lines.len());
+            }
+        }
+
+        FeatureTables {
+            masks,
+            pext_offset,
+            pext_ternary: pext_ternary.into_boxed_slice(),
+            line_range,
+            lines: lines.into_boxed_slice(),
+            line_ternary: line_ternary.into_boxed_slice(),
+            square_weights,
+        }
+    })
+}
+
+/// パターンのマスを1つずつ調べて特徴を求める
+#[inline(always)]
+pub fn calc_features_scalar(p: u64, o: u64) -> FeatureBit {
+    let mut feature_bit = [[0; N_ROTATION]; N_PATTERN];
+    for (fbit, pattern) in feature_bit.iter_mut().zip(&FEATURE_COORD) {
+        for (f, coords) in fbit.iter_mut().zip(&pattern.feature_coord) {
+            for &coord in &coords[..pattern.n_square as usize] {
+                debug_assert_ne!(coord, NO_COORD);
+
+                let color = 2 * (1 & p >> coord) + (1 & o >> coord);
+                *f = *f * 3u16 + color as u16;
+            }
+        }
+    }
+    feature_bit
+}
+
+/// 盤面の行ごとの変換テーブルを用いて特徴を求める
+#[inline(always)]
+pub fn calc_features_table(p: u64, o: u64) -> FeatureBit {
+    let tables = feature_tables();
+
+    // 盤面の各行、転置した盤面の各行のビット列
+    let mut line_bits = [(0u8, 0u8); 16];
+    let (tp, to) = (transpose(p), transpose(o));
+    for row in 0..8 {
+        line_bits[row] = ((p >> (8 * row)) as u8, (o >> (8 * row)) as u8);
+        line_bits[row + 8] = ((tp >> (8 * row)) as u8, (to >> (8 * row)) as u8);
+    }
+
+    let mut feature_bit = [[0; N_ROTATION]; N_PATTERN];
+    for (fbit, ranges) in feature_bit.iter_mut().zip(&tables.line_range) {
+        for (f, &(start, end)) in fbit.iter_mut().zip(ranges) {
+            for line in &tables.lines[start..end] {
+                let (p, o) = line_bits[line.line as usize & 15];
+                let table = &tables.line_ternary[line.table as usize];
+                *f += 2 * table[p as usize] + table[o as usize];
+            }
+        }
+    }
+    feature_bit
+}
+
+/// BMI2 の PEXT 命令を用いて特徴を求める
+///
+/// # Safety
+/// 実行中のCPUが BMI2 に対応していること。
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "bmi2")]
+#[inline]
+pub unsafe fn calc_features_pext(p: u64, o: u64) -> FeatureBit {
+    let tables = feature_tables();
+
+    let mut feature_bit = [[0; N_ROTATION]; N_PATTERN];
+    for ((fbit, masks), offsets) in feature_bit.iter_mut().zip(&tables.masks).zip(&tables.pext_offset) {
+        for ((f, &mask), &offset) in fbit.iter_mut().zip(masks).zip(offsets) {
+            let table = &tables.pext_ternary[offset..];
+            *f = 2 * table[_pext_u64(p, mask) as usize] + table[_pext_u64(o, mask) as usize];
+        }
+    }
+    feature_bit
+}
+
+/// 指定した方法で特徴を求める
+///
+/// `extractor` は実行中のCPUで使える方法であること (`FeatureExtractor::or_supported` で選んだもの)。
+/// 外部から方法を受け取る `FeatureScratch::new` と `IncrementalFeatures::new` で確かめている。
+#[inline(always)]
+pub fn calc_features(p: u64, o: u64, extractor: FeatureExtractor) -> FeatureBit {
+    match extractor {
+        FeatureExtractor::Scalar => calc_features_scalar(p, o),
+        FeatureExtractor::Table => calc_features_table(p, o),
+        FeatureExtractor::Pext => {
+            debug_assert!(extractor.is_supported());
+            #[cfg(target_arch = "x86_64")]
+            // SAFETY: Pext は BMI2 に対応したCPUでのみ選ばれる (`FeatureExtractor::or_supported`)
+            return unsafe { calc_features_pext(p, o) };
+            #[cfg(not(target_arch = "x86_64"))]
+            calc_features_table(p, o)
+        }
+    }
+}
+
+/// 探索中に、着手ごとに差分を更新する特徴
+///
+/// 生成時に指定した一方の側から見た特徴のみを持つ。
+/// 評価に使えるのはその側が手番のときだけなので、葉での手番側を指定して生成する。
+#[derive(Clone)]
+pub struct IncrementalFeatures {
+    features: FeatureBit,
+    /// 特徴を持っている側が手番か
+    is_viewer_turn: bool,
+    extractor: FeatureExtractor,
+}
+
+impl IncrementalFeatures {
+    /// 手番側 (`p`) から見た特徴を計算して生成する
+    ///
+    /// 実行中のCPUで使えない方法を指定した場合は、`Table` を用いる。
+    pub fn new(p: u64, o: u64, extractor: FeatureExtractor) -> Self {
+        Self::with_viewer(p, o, true, extractor.or_supported())
+    }
+
+    /// 手番でない側 (`o`) から見た特徴を計算して生成する
+    ///
+    /// 実行中のCPUで使えない方法を指定した場合は、`Table` を用いる。
+    pub fn new_for_opponent(p: u64, o: u64, extractor: FeatureExtractor) -> Self {
+        Self::with_viewer(p, o, false, extractor.or_supported())
+    }
+
+    /// `is_viewer_turn` で指定した側から見た特徴を計算して生成する
+    ///
+    /// 探索中に呼ばれるため、`extractor` が使えるかは調べない (`or_supported` で選んだものを渡すこと)。
+    #[inline(always)]
+    pub(crate) fn with_viewer(p: u64, o: u64, is_viewer_turn: bool, extractor: FeatureExtractor) -> Self {
+        let features = if is_viewer_turn { calc_features(p, o, extractor) } else { calc_features(o, p, extractor) };
+        Self { features, is_viewer_turn, extractor }
+    }
+
+    /// 生成時に指定した側から見た特徴
+    #[inline(always)]
+    pub fn features(&self) -> &FeatureBit {
+        &self.features
+    }
+
+    /// 生成時に指定した側が手番か
+    #[inline(always)]
+    pub fn is_viewer_turn(&self) -> bool {
+        self.is_viewer_turn
+    }
+
+    /// 生成時に特徴の計算に用いた方法
+    #[inline(always)]
+    pub fn extractor(&self) -> FeatureExtractor {
+        self.extractor
+    }
+
+    /// 手番側が `put` に打ち、`flip` の石を反転させた後の特徴に更新する
+    #[inline(always)]
+    pub fn update(&mut self, put: u64, flip: u64) {
+        let diff = Self::diff(put, flip, self.is_viewer_turn);
+        for (f, d) in self.features.as_flattened_mut().iter_mut().zip(diff) {
+            *f = f.wrapping_add(d);
+        }
+        self.is_viewer_turn = !self.is_viewer_turn;
+    }
+
+    /// `update(put, flip)` の前の特徴に戻す
+    #[inline(always)]
+    pub fn restore(&mut self, put: u64, flip: u64) {
+        self.is_viewer_turn = !self.is_viewer_turn;
+        let diff = Self::diff(put, flip, self.is_viewer_turn);
+        for (f, d) in self.features.as_flattened_mut().iter_mut().zip(diff) {
+            *f = f.wrapping_sub(d);
+        }
+    }
+
+    /// パスする (手番のみ入れ替える)
+    #[inline(always)]
+    pub fn pass(&mut self) {
+        self.is_viewer_turn = !self.is_viewer_turn;
+    }
+
+    /// 着手による特徴の変化量
+    ///
+    /// 自分の手番なら 空き(0) -> 自分(2), 相手(1) -> 自分(2)
+    /// 相手の手番なら 空き(0) -> 相手(1), 自分(2) -> 相手(1)
+    #[inline(always)]
+    fn diff(put: u64, flip: u64, is_viewer_turn: bool) -> [u16; N_FEAUTURE] {
+        let (put_weights, flip_weights) = Self::weights(put, flip);
+        let mut diff = [0u16; N_FEAUTURE];
+        let pairs = diff.iter_mut().zip(put_weights).zip(flip_weights);
+        if is_viewer_turn {
+            for ((d, w_put), w_flip) in pairs {
+                *d = w_put.wrapping_mul(2).wrapping_add(w_flip);
+            }
+        } else {
+            for ((d, w_put), w_flip) in pairs {
+                *d = w_put.wrapping_sub(w_flip);
+            }
+        }
+        diff
+    }
+
+    /// 着手したマスの重みと、反転したマスの重みの和
+    #[inline(always)]
+    fn weights(put: u64, flip: u64) -> ([u16; N_FEAUTURE], [u16; N_FEAUTURE]) {
+        let square_weights = &feature_tables().square_weights;
+        let put_weights: [u16; N_FEAUTURE] = square_weights[put.trailing_zeros() as usize].as_flattened().try_into().unwrap();
+        let mut flip_weights = [0u16; N_FEAUTURE];
+        let mut flip = flip;
+        while flip != 0 {
+            let weights = square_weights[flip.trailing_zeros() as usize].as_flattened();
+            for (w, &w_flip) in flip_weights.iter_mut().zip(weights) {
+                *w += w_flip;
+            }
+            flip &= flip - 1;
+        }
+        (put_weights, flip_weights)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::eval_simple::put_random_piece;
+    use crate::move_list::MoveIterator;
+    use rand::Rng;
+
+    #[test]
+    fn extractors_match_scalar() {
+        for _ in 0..100 {
+            let mut board = Board::new();
+            while board.moves() != 0 {
+                let (p, o) = (board.player, board.opponent);
+                let expected = calc_features_scalar(p, o);
+                assert_eq!(calc_features_table(p, o), expected);
+                #[cfg(target_arch = "x86_64")]
+                if FeatureExtractor::Pext.is_supported() {
+                    assert_eq!(unsafe { calc_features_pext(p, o) }, expected);
+                }
+                put_random_piece(&mut board).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn unsupported_extractor_falls_back_to_table() {
+        for extractor in [FeatureExtractor::Scalar, FeatureExtractor::Table, FeatureExtractor::Pext] {
+            let chosen = extractor.or_supported();
+            assert!(chosen.is_supported());
+            assert_eq!(chosen == extractor, extractor.is_supported());
+            let features = IncrementalFeatures::new(0x0000_0008_1000_0000, 0x0000_0010_0800_0000, extractor);
+            assert_eq!(features.extractor, chosen);
+        }
+    }
+
+    #[test]
+    fn incremental_features_match_full_computation() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let mut board = Board::new();
+            // 黒から見た特徴
+            let mut features = IncrementalFeatures::new(board.player, board.opponent, FeatureExtractor::Table);
+            let black_view = |board: &Board, is_black_turn: bool| {
+                if is_black_turn {
+                    calc_features_scalar(board.player, board.opponent)
+                } else {
+                    calc_features_scalar(board.opponent, board.player)
+                }
+            };
+            // (着手前の盤面, 着手位置, 反転した石), 着手位置が 0 ならパス
+            let mut history = Vec::new();
+
+            // 終局まで打ち進める
+            loop {
+                assert_eq!(*features.features(), black_view(&board, features.is_viewer_turn()));
+                let moves = board.moves();
+                if moves == 0 {
+                    if board.opponent_moves() == 0 {
+                        break;
+                    }
+                    history.push((board.clone(), 0, 0));
+                    board.swap();
+                    features.pass();
+                    continue;
+                }
+                let put = MoveIterator::new(moves).nth(rng.gen_range(0..moves.count_ones() as usize)).unwrap();
+                let flip = board.flip_bit(put);
+                history.push((board.clone(), put, flip));
+                board.put_piece_fast(put);
+                features.update(put, flip);
+            }
+
+            // 初期局面まで戻す
+            while let Some((prev_board, put, flip)) = history.pop() {
+                if put == 0 {
+                    features.pass();
+                } else {
+                    features.restore(put, flip);
+                }
+                assert_eq!(*features.features(), black_view(&prev_board, features.is_viewer_turn()));
+            }
+        }
+    }
+}
@@ -0,0 +1,363 @@
+// Reading and writing evaluation weights (JSON and compact binary format)
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+
+use crate::eval::evaluator_const::*;
+use crate::eval::{EvaluationScores, Evaluator};
+
+/// 評価関数のバイナリファイルの識別子
+const BINARY_MAGIC: [u8; 4] = *b"DREV";
+/// バイナリ形式のバージョン (形式を変えたら更新する)
+const BINARY_VERSION: u32 = 1;
+/// 手番 (空きマス数の偶奇) ごとの評価値の数
+const N_PARITY: usize = 2;
+
+/// 評価関数の読み込みに失敗した
+#[derive(Debug)]
+pub enum EvaluatorError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    /// 評価関数のバイナリファイルではない
+    InvalidMagic,
+    /// 対応していない形式のバージョン
+    UnsupportedVersion(u32),
+    /// パターンの構成やフェーズ数などが、このバージョンのエンジンと一致しない
+    LayoutMismatch(String),
+    /// データが途中で終わっている
+    Truncated,
+    /// チェックサムが一致しない (ファイルが壊れている)
+    ChecksumMismatch { expected: u32, actual: u32 },
+}
+
+impl fmt::Display for EvaluatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvaluatorError::Io(e) => write!(f, "failed to read evaluator: {}", e),
+            EvaluatorError::Json(e) => write!(f, "failed to parse evaluator JSON: {}", e),
+            EvaluatorError::InvalidMagic => f.write_str("not an evaluator binary file"),
+            EvaluatorError::UnsupportedVersion(v) => {
+                write!(f, "unsupported evaluator binary version {} (expected {})", v, BINARY_VERSION)
+            }
+            EvaluatorError::LayoutMismatch(s) => write!(f, "evaluator layout mismatch: {}", s),
+            EvaluatorError::Truncated => f.write_str("evaluator binary file is truncated"),
+            EvaluatorError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "evaluator checksum mismatch (expected {:08x}, got {:08x})",
+                expected, actual
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EvaluatorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EvaluatorError::Io(e) => Some(e),
+            EvaluatorError::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for EvaluatorError {
+    fn from(e: io::Error) -> Self {
+        EvaluatorError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for EvaluatorError {
+    fn from(e: serde_json::Error) -> Self {
+        EvaluatorError::Json(e)
+    }
+}
+
+impl Evaluator {
+    /// ファイルから評価関数を読み込む
+    ///
+    /// 先頭の識別子でバイナリ形式か JSON 形式かを判別する。
+    pub fn read_file(path: &str) -> Result<Evaluator, EvaluatorError> {
+        Self::read_bytes(&fs::read(path)?)
+    }
+
+    /// バイナリ形式または JSON 形式のデータから評価関数を読み込む
+    pub fn read_bytes(input: &[u8]) -> Result<Evaluator, EvaluatorError> {
+        if input.starts_with(&BINARY_MAGIC) {
+            Self::from_binary(input)
+        } else {
+            let evaluator: Evaluator = serde_json::from_slice(input)?;
+            evaluator.check_layout()?;
+            Ok(evaluator)
+        }
+    }
+
+    /// JSON 形式の文字列から評価関数を読み込む
+    pub fn read_string(input: &str) -> Result<Evaluator, EvaluatorError> {
+        let evaluator: Evaluator = serde_json::from_str(input)?;
+        evaluator.check_layout()?;
+        Ok(evaluator)
+    }
+
+    /// JSON 形式で保存する
+    pub fn write_file(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()
+    }
+
+    /// バイナリ形式で保存する
+    pub fn write_binary_file(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_binary())
+    }
+
+    /// バイナリ形式に変換する
+    ///
+    /// 形式 (数値は全てリトルエンディアン):
+    /// - 識別子 `DREV`, 形式のバージョン (u32)
+    /// - パターン数 (u32), 各パターンの評価値の数 (u32 * パターン数)
+    /// - 着手可能数の差の評価値の数 (u32), 手番の数 (u32), フェーズ数 (u32)
+    /// - 評価関数のバージョン (u32 の長さ + UTF-8), `n_deta_set` (i32), `n_iteration` (i32)
+    /// - 評価値のチェックサム (u32, FNV-1a)
+    /// - 評価値 (i16): 手番, フェーズごとに、各パターン, 着手可能数の差, 定数項の順
+    pub fn to_binary(&self) -> Vec<u8> {
+        let tables = self.table_bytes();
+
+        let mut out = Vec::with_capacity(tables.len() + 128);
+        out.extend_from_slice(&BINARY_MAGIC);
+        out.extend_from_slice(&BINARY_VERSION.to_le_bytes());
+        out.extend_from_slice(&(N_PATTERN as u32).to_le_bytes());
+        for n in N_FEATURE_POSITIONS {
+            out.extend_from_slice(&(n as u32).to_le_bytes());
+        }
+        out.extend_from_slice(&(N_MOBILITY_MAX as u32).to_le_bytes());
+        out.extend_from_slice(&(self.eval.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(self.eval.first().map_or(0, |e| e.len()) as u32).to_le_bytes());
+        out.extend_from_slice(&(self.version.len() as u32).to_le_bytes());
+        out.extend_from_slice(self.version.as_bytes());
+        out.extend_from_slice(&self.n_deta_set.to_le_bytes());
+        out.extend_from_slice(&self.n_iteration.to_le_bytes());
+        out.extend_from_slice(&checksum(&tables).to_le_bytes());
+        out.extend_from_slice(&tables);
+        out
+    }
+
+    /// 評価値のチェックサム (FNV-1a, バイナリ形式に記録する値と同じ)
+    ///
+    /// バージョンが同じでも、重みが異なる評価関数を区別するために使う。
+    pub fn weights_checksum(&self) -> u32 {
+        checksum(&self.table_bytes())
+    }
+
+    /// 評価値をバイナリ形式と同じ順に並べたバイト列
+    fn table_bytes(&self) -> Vec<u8> {
+        let mut tables = Vec::new();
+        for scores in self.eval.iter().flatten() {
+            for &e in scores.pattern_eval.iter().flatten().chain(&scores.mobility_eval) {
+                tables.extend_from_slice(&e.to_le_bytes());
+            }
+            tables.extend_from_slice(&scores.const_eval.to_le_bytes());
+        }
+        tables
+    }
+
+    /// `to_binary` で変換したデータから評価関数を読み込む
+    pub fn from_binary(input: &[u8]) -> Result<Evaluator, EvaluatorError> {
+        let mut reader = ByteReader { input };
+        if reader.take(BINARY_MAGIC.len())? != BINARY_MAGIC {
+            return Err(EvaluatorError::InvalidMagic);
+        }
+        let version = reader.u32()?;
+        if version != BINARY_VERSION {
+            return Err(EvaluatorError::UnsupportedVersion(version));
+        }
+
+        let n_pattern = reader.u32()? as usize;
+        if n_pattern != N_PATTERN {
+            return Err(layout_mismatch("patterns", N_PATTERN, n_pattern));
+        }
+        for n in N_FEATURE_POSITIONS {
+            let n_positions = reader.u32()? as usize;
+            if n_positions != n {
+                return Err(layout_mismatch("pattern positions", n, n_positions));
+            }
+        }
+        let n_mobility = reader.u32()? as usize;
+        if n_mobility != N_MOBILITY_MAX {
+            return Err(layout_mismatch("mobility positions", N_MOBILITY_MAX, n_mobility));
+        }
+        let n_parity = reader.u32()? as usize;
+        if n_parity != N_PARITY {
+            return Err(layout_mismatch("parities", N_PARITY, n_parity));
+        }
+        let n_phase = reader.u32()? as usize;
+        if n_phase != N_PHASE {
+            return Err(layout_mismatch("phases", N_PHASE, n_phase));
+        }
+
+        let version_len = reader.u32()? as usize;
+        let eval_version = String::from_utf8(reader.take(version_len)?.to_vec())
+            .map_err(|_| EvaluatorError::LayoutMismatch("version is not valid UTF-8".to_string()))?;
+        let n_deta_set = reader.i32()?;
+        let n_iteration = reader.i32()?;
+
+        let expected = reader.u32()?;
+        let n_scores = N_FEATURE_POSITIONS.iter().sum::<usize>() + N_MOBILITY_MAX + 1;
+        let tables = reader.take(N_PARITY * N_PHASE * n_scores * 2)?;
+        if !reader.input.is_empty() {
+            return Err(EvaluatorError::LayoutMismatch("unexpected data after the tables".to_string()));
+        }
+        let actual = checksum(tables);
+        if actual != expected {
+            return Err(EvaluatorError::ChecksumMismatch { expected, actual });
+        }
+
+        let mut values = tables.chunks_exact(2).map(|b| i16::from_le_bytes([b[0], b[1]]));
+        let mut read_scores = || EvaluationScores {
+            pattern_eval: N_FEATURE_POSITIONS
+                .iter()
+                .map(|&n| values.by_ref().take(n).collect())
+                .collect(),
+            mobility_eval: values.by_ref().take(N_MOBILITY_MAX).collect(),
+            const_eval: values.next().unwrap(),
+        };
+        let mut eval = Vec::with_capacity(N_PARITY);
+        for _ in 0..N_PARITY {
+            eval.push((0..N_PHASE).map(|_| read_scores()).collect());
+        }
+
+        Ok(Evaluator {
+            version: eval_version,
+            n_deta_set,
+            n_iteration,
+            eval,
+        })
+    }
+
+    /// 評価値の表の大きさが、このバージョンのエンジンと一致するか調べる
+    fn check_layout(&self) -> Result<(), EvaluatorError> {
+        if self.eval.len() != N_PARITY {
+            return Err(layout_mismatch("parities", N_PARITY, self.eval.len()));
+        }
+        for phases in &self.eval {
+            if phases.len() != N_PHASE {
+                return Err(layout_mismatch("phases", N_PHASE, phases.len()));
+            }
+            for scores in phases {
+                if scores.pattern_eval.len() != N_PATTERN {
+                    return Err(layout_mismatch("patterns", N_PATTERN, scores.pattern_eval.len()));
+                }
+                for (pattern, &n) in scores.pattern_eval.iter().zip(&N_FEATURE_POSITIONS) {
+                    if pattern.len() != n {
+                        return Err(layout_mismatch("pattern positions", n, pattern.len()));
+                    }
+                }
+                if scores.mobility_eval.len() != N_MOBILITY_MAX {
+                    return Err(layout_mismatch("mobility positions", N_MOBILITY_MAX, scores.mobility_eval.len()));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn layout_mismatch(what: &str, expected: usize, actual: usize) -> EvaluatorError {
+    EvaluatorError::LayoutMismatch(format!("expected {} {}, got {}", expected, what, actual))
+}
+
+/// FNV-1a (32bit)
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5, |h, &b| (h ^ b as u32).wrapping_mul(0x01000193))
+}
+
+/// バイト列を先頭から読み進める
+struct ByteReader<'a> {
+    input: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], EvaluatorError> {
+        if self.input.len() < n {
+            return Err(EvaluatorError::Truncated);
+        }
+        let (head, rest) = self.input.split_at(n);
+        self.input = rest;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32, EvaluatorError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> Result<i32, EvaluatorError> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_evaluator() -> Evaluator {
+        let mut evaluator = Evaluator {
+            version: "test".to_string(),
+            n_deta_set: 12,
+            n_iteration: 34,
+            ..Evaluator::zero()
+        };
+        let mut x = 0i16;
+        for scores in evaluator.eval.iter_mut().flatten() {
+            for e in scores.pattern_eval.iter_mut().flatten().chain(scores.mobility_eval.iter_mut()) {
+                x = x.wrapping_mul(31).wrapping_add(7);
+                *e = x;
+            }
+            scores.const_eval = x;
+        }
+        evaluator
+    }
+
+    fn assert_same(a: &Evaluator, b: &Evaluator) {
+        assert_eq!((&a.version, a.n_deta_set, a.n_iteration), (&b.version, b.n_deta_set, b.n_iteration));
+        for (x, y) in a.eval.iter().flatten().zip(b.eval.iter().flatten()) {
+            assert_eq!(x.pattern_eval, y.pattern_eval);
+            assert_eq!(x.mobility_eval, y.mobility_eval);
+            assert_eq!(x.const_eval, y.const_eval);
+        }
+    }
+
+    #[test]
+    fn binary_and_json_roundtrip() {
+        let evaluator = sample_evaluator();
+
+        let binary = evaluator.to_binary();
+        assert_same(&Evaluator::read_bytes(&binary).unwrap(), &evaluator);
+
+        let json = serde_json::to_string(&evaluator).unwrap();
+        assert_same(&Evaluator::read_bytes(json.as_bytes()).unwrap(), &evaluator);
+        assert_same(&Evaluator::read_string(&json).unwrap(), &evaluator);
+    }
+
+    #[test]
+    fn broken_input_is_an_error() {
+        let binary = sample_evaluator().to_binary();
+
+        let mut corrupted = binary.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert!(matches!(Evaluator::from_binary(&corrupted), Err(EvaluatorError::ChecksumMismatch { .. })));
+
+        let truncated = &binary[..binary.len() - 1];
+        assert!(matches!(Evaluator::from_binary(truncated), Err(EvaluatorError::Truncated)));
+
+        let mut future = binary.clone();
+        future[4] = 99;
+        assert!(matches!(Evaluator::from_binary(&future), Err(EvaluatorError::UnsupportedVersion(99))));
+
+        assert!(matches!(Evaluator::read_bytes(b"{\"version\": "), Err(EvaluatorError::Json(_))));
+        assert!(matches!(Evaluator::read_file("no/such/eval.bin"), Err(EvaluatorError::Io(_))));
+
+        let mut evaluator = sample_evaluator();
+        evaluator.eval[1].pop();
+        let json = serde_json::to_string(&evaluator).unwrap();
+        assert!(matches!(Evaluator::read_string(&json), Err(EvaluatorError::LayoutMismatch(_))));
+    }
+}
@@ -4,7 +4,12 @@ use crate::board::*;
 use crate::perfect_search::solve_score;
 use crate::cut_off::*;
 use crate::move_list::*;
-use crate::solver::SearchEngine;
+use crate::solver::{SearchEngine, SearchStats};
+use crate::eval::{Evaluator, FeatureScratch};
+use crate::evaluate::Evaluate;
+use crate::eval_feature::{FeatureExtractor, IncrementalFeatures};
+#[cfg(debug_assertions)]
+use crate::eval_feature::calc_features;
 
 use crate::mpc::*;
 use crate::N_TT_MOVES;
@@ -17,9 +22,13 @@ const MOVE_ORDERING_EVAL_LEVEL_SIMPLE_SEARCH: i32 = 1;
 const SWITCH_SIMPLE_SEARCH_LEVEL: i32 = 6;
 const SWITCH_NEGAALPHA_SEARCH_LEVEL: i32 = 4;
 
+/// move ordering を行わない、残り `lv` 手の評価関数による探索
+///
+/// パターン評価関数の場合、特徴はこの関数に入るときに盤面から計算し、その下の局面では差分で更新する。
+/// 特徴は片方の手番から見たものしか持たないため、上位の探索 (`nws_eval`, `pvs_eval` や move ordering) からは引き継がない。
 pub fn negaalpha_eval_no_mo(
     board: &Board,
-    mut alpha: i32,
+    alpha: i32,
     beta: i32,
     lv: i32,
     search: &mut SearchEngine,
@@ -27,10 +36,54 @@ pub fn negaalpha_eval_no_mo(
     #[cfg(debug_assertions)]
     assert!(alpha <= beta);
 
-    search.status.eval_search_node_count += 1;
     if lv <= 0 {
+        search.status.eval_search_node_count += 1;
         search.status.eval_search_leaf_node_count += 1;
-        return search.eval_func.clac_features_eval(board);
+        return search.eval_func.evaluate(board, &mut search.eval_scratch);
+    }
+
+    let SearchEngine { eval_func, eval_scratch, status, .. } = search;
+    match eval_func.as_pattern_evaluator() {
+        Some(evaluator) => {
+            // 葉の特徴は、この局面の特徴から差分で計算する
+            let mut features = leaf_features(board, lv, eval_scratch.feature_extractor());
+            negaalpha_eval_incremental(board, alpha, beta, lv, &mut features, evaluator, status)
+        }
+        None => negaalpha_eval_leaf(board, alpha, beta, lv, eval_func.as_ref(), eval_scratch, status),
+    }
+}
+
+/// `lv` 手先の局面で手番になる側から見た特徴
+///
+/// パスがなければ、葉での手番は `lv` の偶奇で決まる。
+fn leaf_features(board: &Board, lv: i32, extractor: FeatureExtractor) -> IncrementalFeatures {
+    IncrementalFeatures::with_viewer(board.player, board.opponent, lv % 2 == 0, extractor)
+}
+
+/// パターン評価関数を用いる場合の `negaalpha_eval_no_mo` の本体
+///
+/// `features` は `lv` 手先の手番側から見た特徴で、子局面を探索する間は差分で更新し、戻るときに元に戻す。
+fn negaalpha_eval_incremental(
+    board: &Board,
+    mut alpha: i32,
+    beta: i32,
+    lv: i32,
+    features: &mut IncrementalFeatures,
+    evaluator: &Evaluator,
+    status: &mut SearchStats,
+) -> i32 {
+    #[cfg(debug_assertions)]
+    {
+        assert!(alpha <= beta);
+        assert_eq!(features.is_viewer_turn(), lv % 2 == 0);
+        let (p, o) = if features.is_viewer_turn() { (board.player, board.opponent) } else { (board.opponent, board.player) };
+        assert_eq!(*features.features(), calc_features(p, o, FeatureExtractor::Scalar));
+    }
+
+    status.eval_search_node_count += 1;
+    if lv <= 0 {
+        status.eval_search_leaf_node_count += 1;
+        return evaluator.scaled_eval_from_features(board, features.features());
     }
 
     let legal_moves = board.moves();
@@ -41,11 +94,73 @@ pub fn negaalpha_eval_no_mo(
         board.swap();
         if board.moves() == 0 {
             // passしても置くところがない == ゲーム終了
-            search.status.eval_search_leaf_node_count += 1;
+            status.eval_search_leaf_node_count += 1;
             board.swap();
             return solve_score(&board);
         }
-        return -negaalpha_eval_no_mo(&board, -beta, -alpha, lv, search);
+        // 葉での手番が入れ替わるので、特徴を計算し直す
+        let mut features = leaf_features(&board, lv, features.extractor());
+        return -negaalpha_eval_incremental(&board, -beta, -alpha, lv, &mut features, evaluator, status);
+    }
+
+    // 探索範囲: [alpha, beta]
+
+    let mut best_score = -SCORE_INF;
+    for l in MoveIterator::new(legal_moves) {
+        let flip = board.flip_bit(l);
+        let mut next_board = board.clone();
+        next_board.put_piece_fast_from_flip_bit(l, flip);
+
+        features.update(l, flip);
+        let score = -negaalpha_eval_incremental(&next_board, -beta, -alpha, lv - 1, features, evaluator, status);
+        features.restore(l, flip);
+
+        if score >= beta {
+            return score;
+        }
+        if score > alpha {
+            alpha = score
+        };
+        if score > best_score {
+            best_score = score
+        };
+    }
+
+    best_score
+}
+
+/// パターン評価関数以外を用いる場合の `negaalpha_eval_no_mo` の本体 (葉で毎回評価する)
+fn negaalpha_eval_leaf(
+    board: &Board,
+    mut alpha: i32,
+    beta: i32,
+    lv: i32,
+    eval_func: &dyn Evaluate,
+    scratch: &mut FeatureScratch,
+    status: &mut SearchStats,
+) -> i32 {
+    #[cfg(debug_assertions)]
+    assert!(alpha <= beta);
+
+    status.eval_search_node_count += 1;
+    if lv <= 0 {
+        status.eval_search_leaf_node_count += 1;
+        return eval_func.evaluate(board, scratch);
+    }
+
+    let legal_moves = board.moves();
+
+    // 合法手がない
+    if legal_moves == 0 {
+        let mut board = board.clone();
+        board.swap();
+        if board.moves() == 0 {
+            // passしても置くところがない == ゲーム終了
+            status.eval_search_leaf_node_count += 1;
+            board.swap();
+            return solve_score(&board);
+        }
+        return -negaalpha_eval_leaf(&board, -beta, -alpha, lv, eval_func, scratch, status);
     }
 
     // 探索範囲: [alpha, beta]
@@ -54,7 +169,7 @@ pub fn negaalpha_eval_no_mo(
     for l in MoveIterator::new(legal_moves) {
         let mut board = board.clone();
         board.put_piece_fast(l);
-        let score = -negaalpha_eval_no_mo(&board, -beta, -alpha, lv - 1, search);
+        let score = -negaalpha_eval_leaf(&board, -beta, -alpha, lv - 1, eval_func, scratch, status);
         if score >= beta {
             return score;
         }
@@ -357,6 +472,11 @@ pub fn nws_eval(board: &Board, mut alpha: i32, lv: i32, search: &mut SearchEngin
         return nws_eval_simple(board, alpha, lv, search);
     }
 
+    // 打ち切られた探索の結果は使われないため、任意の値を返してよい
+    if search.check_limits() {
+        return 0;
+    }
+
     search.status.eval_search_node_count += 1;
     // 探索範囲: [alpha, beta]
     let mut moves_bit: u64 = board.moves();
@@ -454,7 +574,7 @@ pub fn nws_eval(board: &Board, mut alpha: i32, lv: i32, search: &mut SearchEngin
             }
             let score: i32 = -nws_eval(&move_board.board, -beta, lv - 1, search);
             if score >= beta {
-                search.t_table.add(
+                search.t_table_add(
                     board,
                     score,
                     SCORE_INF,
@@ -486,7 +606,7 @@ pub fn nws_eval(board: &Board, mut alpha: i32, lv: i32, search: &mut SearchEngin
         }
         let score = -nws_eval(&move_board.board, -beta, lv - 1, search);
         if score >= beta {
-            search.t_table.add(
+            search.t_table_add(
                 board,
                 score,
                 SCORE_INF,
@@ -509,7 +629,7 @@ pub fn nws_eval(board: &Board, mut alpha: i32, lv: i32, search: &mut SearchEngin
         return -SCORE_INF;
     }
     if best_score > alpha {
-        search.t_table.add(
+        search.t_table_add(
             board,
             best_score,
             best_score,
@@ -518,7 +638,7 @@ pub fn nws_eval(board: &Board, mut alpha: i32, lv: i32, search: &mut SearchEngin
             best_move,
         );
     } else {
-        search.t_table.add(
+        search.t_table_add(
             board,
             -SCORE_INF,
             best_score,
@@ -569,6 +689,11 @@ pub fn pvs_eval(board: &Board, mut alpha: i32, mut beta: i32, lv: i32, search: &
         return pvs_eval_simple(board, alpha, beta, lv, search);
     }
 
+    // 打ち切られた探索の結果は使われないため、任意の値を返してよい
+    if search.check_limits() {
+        return 0;
+    }
+
     search.status.eval_search_node_count += 1;
 
     #[cfg(debug_assertions)]
@@ -681,7 +806,7 @@ pub fn pvs_eval(board: &Board, mut alpha: i32, mut beta: i32, lv: i32, search: &
             if !pvs_ok {
                 let score: i32 = -pvs_eval(&move_board.board, -beta, -alpha, lv - 1, search);
                 if score >= beta {
-                    search.t_table.add(
+                    search.t_table_add(
                         board,
                         score,
                         SCORE_INF,
@@ -703,7 +828,7 @@ pub fn pvs_eval(board: &Board, mut alpha: i32, mut beta: i32, lv: i32, search: &
                 let put_board = &move_board.board;
                 let mut score = -nws_eval(put_board, -this_node_alpha - 1, lv - 1, search);
                 if score >= beta {
-                    search.t_table.add(
+                    search.t_table_add(
                         board,
                         score,
                         SCORE_INF,
@@ -717,7 +842,7 @@ pub fn pvs_eval(board: &Board, mut alpha: i32, mut beta: i32, lv: i32, search: &
                     // 再探索
                     score = -pvs_eval(put_board, -beta, -this_node_alpha, lv - 1, search);
                     if score >= beta {
-                        search.t_table.add(
+                        search.t_table_add(
                             board,
                             score,
                             SCORE_INF,
@@ -751,7 +876,7 @@ pub fn pvs_eval(board: &Board, mut alpha: i32, mut beta: i32, lv: i32, search: &
             best_move = move_board.put_place;
             best_score = -pvs_eval(&move_board.board, -beta, -this_node_alpha, lv - 1, search);
             if best_score >= beta {
-                search.t_table.add(
+                search.t_table_add(
                     board,
                     best_score,
                     SCORE_INF,
@@ -775,7 +900,7 @@ pub fn pvs_eval(board: &Board, mut alpha: i32, mut beta: i32, lv: i32, search: &
         let put_board = &move_board.board;
         let mut score = -nws_eval(put_board, -this_node_alpha - 1, lv - 1, search);
         if score >= beta {
-            search.t_table.add(
+            search.t_table_add(
                 board,
                 score,
                 SCORE_INF,
@@ -789,7 +914,7 @@ pub fn pvs_eval(board: &Board, mut alpha: i32, mut beta: i32, lv: i32, search: &
             // 再探索
             score = -pvs_eval(put_board, -beta, -this_node_alpha, lv - 1, search);
             if score >= beta {
-                search.t_table.add(
+                search.t_table_add(
                     board,
                     score,
                     SCORE_INF,
@@ -812,7 +937,7 @@ pub fn pvs_eval(board: &Board, mut alpha: i32, mut beta: i32, lv: i32, search: &
     }
     if best_score > alpha {
         // alpha < best_score < beta
-        search.t_table.add(
+        search.t_table_add(
             board,
             best_score,
             best_score,
@@ -822,7 +947,7 @@ pub fn pvs_eval(board: &Board, mut alpha: i32, mut beta: i32, lv: i32, search: &
         );
     } else {
         // best_score <= alpha
-        search.t_table.add(
+        search.t_table_add(
             board,
             -SCORE_INF,
             best_score,
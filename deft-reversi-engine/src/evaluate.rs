@@ -0,0 +1,107 @@
+// Evaluation function interface used by the search
+
+use std::sync::Arc;
+
+use crate::board::Board;
+use crate::eval::evaluator_const::SCORE_MAX;
+use crate::eval::{Evaluator, FeatureScratch};
+use crate::eval_simple::simplest_eval;
+
+/// 探索で用いる評価関数
+///
+/// `SearchEngine` はこのトレイトを通して評価関数を呼び出すため、
+/// 探索を変更せずに評価関数を差し替えられる。
+/// 探索スレッド間で共有するため、`Send + Sync` である必要がある。
+pub trait Evaluate: Send + Sync {
+    /// 手番側から見た評価値 (石差, `-SCORE_MAX..=SCORE_MAX`)
+    ///
+    /// `scratch` は探索エンジンごとの作業領域で、使わなくてもよい。
+    fn evaluate(&self, board: &Board, scratch: &mut FeatureScratch) -> i32;
+
+    /// 評価関数のバージョン
+    ///
+    /// 置換表のスナップショットを読み込むときに、保存時と同じ評価関数か確認するために使う。
+    fn version(&self) -> &str;
+
+    /// 評価関数の重みのチェックサム
+    ///
+    /// バージョンと同じく、置換表のスナップショットが同じ評価関数で作られたか確認するために使う。
+    /// 重みを持たない評価関数は 0 を返す。
+    fn weights_checksum(&self) -> u32 {
+        0
+    }
+
+    /// パターン評価関数であれば、その重み
+    ///
+    /// 葉に近いノードで特徴を差分計算する (`IncrementalFeatures`) ために使う。
+    fn as_pattern_evaluator(&self) -> Option<&Evaluator> {
+        None
+    }
+}
+
+impl Evaluate for Evaluator {
+    #[inline(always)]
+    fn evaluate(&self, board: &Board, scratch: &mut FeatureScratch) -> i32 {
+        self.clac_features_eval(board, scratch)
+    }
+
+    fn version(&self) -> &str {
+        &self.version
+    }
+
+    fn weights_checksum(&self) -> u32 {
+        Evaluator::weights_checksum(self)
+    }
+
+    fn as_pattern_evaluator(&self) -> Option<&Evaluator> {
+        Some(self)
+    }
+}
+
+/// 石の位置・着手可能数・確定した辺による簡易な評価関数 (`simplest_eval`)
+///
+/// 学習済みの評価関数が無くても動作する。
+/// MPC のパラメータはパターン評価関数に合わせてあるため、MPC を使う探索レベルでは精度が落ちる。
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SimpleEvaluator;
+
+impl SimpleEvaluator {
+    /// `simplest_eval` の値を、およそ石差の大きさに縮める割合
+    const SCALE: i32 = 4;
+}
+
+impl Evaluate for SimpleEvaluator {
+    fn evaluate(&self, board: &Board, _scratch: &mut FeatureScratch) -> i32 {
+        (simplest_eval(board) / Self::SCALE).clamp(-SCORE_MAX, SCORE_MAX)
+    }
+
+    fn version(&self) -> &str {
+        "simple"
+    }
+}
+
+/// `SearchEngine` や `Solver` に渡せる評価関数
+///
+/// 評価関数そのもの (`Evaluator` など) を渡すと `Arc` に包んで使い、
+/// `Arc` を渡すと複数の `Solver` で共有する。
+pub trait IntoEvaluator {
+    fn into_evaluator(self) -> Arc<dyn Evaluate>;
+}
+
+impl<E: Evaluate + 'static> IntoEvaluator for E {
+    fn into_evaluator(self) -> Arc<dyn Evaluate> {
+        Arc::new(self)
+    }
+}
+
+impl<E: Evaluate + 'static> IntoEvaluator for Arc<E> {
+    fn into_evaluator(self) -> Arc<dyn Evaluate> {
+        self
+    }
+}
+
+impl IntoEvaluator for Arc<dyn Evaluate> {
+    fn into_evaluator(self) -> Arc<dyn Evaluate> {
+        self
+    }
+}
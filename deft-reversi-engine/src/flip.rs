@@ -6,17 +6,17 @@
 //    Explanation by Okuhara : http://www.amy.hi-ho.ne.jp/okuhara/bitboard.htm
 
 
-#[cfg(target_feature = "avx2")]
+#[cfg(target_arch = "x86_64")]
 use std::arch::x86_64::*;
 
 
-#[cfg(target_feature = "avx2")]
+#[cfg(target_arch = "x86_64")]
 #[repr(align(64))]
 #[derive(Copy, Clone)]
 pub struct V8DI(pub [u64; 8]);
 
 
-#[cfg(target_feature = "avx2")]
+#[cfg(target_arch = "x86_64")]
 const LR_MASK: [V8DI; 66] = [
 	V8DI([ 0x00000000000000fe, 0x0101010101010100, 0x8040201008040200, 0x0000000000000000, 0x0000000000000000, 0x0000000000000000, 0x0000000000000000, 0x0000000000000000 ]),
 	V8DI([ 0x00000000000000fc, 0x0202020202020200, 0x0080402010080400, 0x0000000000000100, 0x0000000000000001, 0x0000000000000000, 0x0000000000000000, 0x0000000000000000 ]),
@@ -98,8 +98,9 @@ const LR_MASK: [V8DI; 66] = [
 //     ]
 // }
 
-#[cfg(target_feature = "avx2")]
-#[inline(always)]
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+#[inline]
 pub unsafe fn load_256(v: &V8DI) -> [__m256i; 2] {
     // v.0.as_ptr()は *const u64、つまり先頭ポインタ
     // これを __m256i(32バイト) のポインタにキャストしてロード
@@ -116,11 +117,12 @@ pub unsafe fn load_256(v: &V8DI) -> [__m256i; 2] {
 // }
 
 
-#[cfg(target_feature = "avx2")]
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
 #[inline]
-pub unsafe fn mm_flip(OP: __m128i, pos: usize) -> __m128i {
-    let pp = _mm256_broadcastq_epi64(OP);
-    let op_hi = _mm_unpackhi_epi64(OP, OP);
+pub unsafe fn mm_flip(op: __m128i, pos: usize) -> __m128i {
+    let pp = _mm256_broadcastq_epi64(op);
+    let op_hi = _mm_unpackhi_epi64(op, op);
     let oo = _mm256_broadcastq_epi64(op_hi);
 
     let masks = load_256(&LR_MASK[pos]);
@@ -169,7 +171,8 @@ pub unsafe fn mm_flip(OP: __m128i, pos: usize) -> __m128i {
     )
 }
 
-#[cfg(target_feature = "avx2")]
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
 #[inline]
 pub unsafe fn flip_avx2(x: usize, p: u64, o: u64) -> u64 {
     let op = _mm_set_epi64x(o as i64, p as i64);
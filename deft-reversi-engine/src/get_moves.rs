@@ -5,61 +5,76 @@
 //    Edax src               : https://github.com/abulmo/edax-reversi
 //    Explanation by Okuhara : http://www.amy.hi-ho.ne.jp/okuhara/bitboard.htm#mobility
 
-#[cfg(target_feature = "avx2")]
+#[cfg(target_arch = "x86_64")]
 use std::arch::x86_64::*;
-#[cfg(target_feature = "avx2")]
+
+#[cfg(target_arch = "x86_64")]
+use crate::cpu::use_avx2;
+
+/// 着手可能位置を求める
+///
+/// 実行時に選択された実装 (`cpu::Backend`) を用いる。
 #[inline(always)]
 pub fn get_moves(p: u64, o: u64) -> u64 {
-    unsafe {
-        let shift1897 = _mm256_set_epi64x(7, 9, 8, 1);
-        let mfliph = _mm256_set_epi64x(
-            0x7e7e7e7e7e7e7e7e, 
-            0x7e7e7e7e7e7e7e7e, 
-            -1i64 as u64 as i64, 
-            0x7e7e7e7e7e7e7e7e
-        );
-    
-        let pp = _mm256_broadcastq_epi64(_mm_cvtsi64_si128(p as i64));
-        let moo = _mm256_and_si256(
-            _mm256_broadcastq_epi64(_mm_cvtsi64_si128(o as i64)), 
-            mfliph
-        );
-    
-        let mut flip_l = _mm256_and_si256(moo, _mm256_sllv_epi64(pp, shift1897));
-        let mut flip_r = _mm256_and_si256(moo, _mm256_srlv_epi64(pp, shift1897));
-    
-        flip_l = _mm256_or_si256(flip_l, _mm256_and_si256(moo, _mm256_sllv_epi64(flip_l, shift1897)));
-        flip_r = _mm256_or_si256(flip_r, _mm256_and_si256(moo, _mm256_srlv_epi64(flip_r, shift1897)));
-    
-        let pre_l = _mm256_and_si256(moo, _mm256_sllv_epi64(moo, shift1897));
-        let pre_r = _mm256_srlv_epi64(pre_l, shift1897);
-    
-        let shift2 = _mm256_add_epi64(shift1897, shift1897);
-    
-        flip_l = _mm256_or_si256(flip_l, _mm256_and_si256(pre_l, _mm256_sllv_epi64(flip_l, shift2)));
-        flip_r = _mm256_or_si256(flip_r, _mm256_and_si256(pre_r, _mm256_srlv_epi64(flip_r, shift2)));
-    
-        flip_l = _mm256_or_si256(flip_l, _mm256_and_si256(pre_l, _mm256_sllv_epi64(flip_l, shift2)));
-        flip_r = _mm256_or_si256(flip_r, _mm256_and_si256(pre_r, _mm256_srlv_epi64(flip_r, shift2)));
-    
-        let mut mm = _mm256_sllv_epi64(flip_l, shift1897);
-        mm = _mm256_or_si256(mm, _mm256_srlv_epi64(flip_r, shift1897));
-    
-        let m = _mm_or_si128(
-            _mm256_castsi256_si128(mm),
-            _mm256_extracti128_si256(mm, 1)
-        );
-    
-        let m = _mm_or_si128(m, _mm_unpackhi_epi64(m, m));
-    
-        (_mm_cvtsi128_si64(m) as u64) & !(p | o)
+    #[cfg(target_arch = "x86_64")]
+    if use_avx2() {
+        return unsafe { get_moves_avx2(p, o) };
     }
+
+    get_moves_std(p, o)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+#[inline]
+pub unsafe fn get_moves_avx2(p: u64, o: u64) -> u64 {
+    let shift1897 = _mm256_set_epi64x(7, 9, 8, 1);
+    let mfliph = _mm256_set_epi64x(
+        0x7e7e7e7e7e7e7e7e, 
+        0x7e7e7e7e7e7e7e7e, 
+        -1i64 as u64 as i64, 
+        0x7e7e7e7e7e7e7e7e
+    );
+
+    let pp = _mm256_broadcastq_epi64(_mm_cvtsi64_si128(p as i64));
+    let moo = _mm256_and_si256(
+        _mm256_broadcastq_epi64(_mm_cvtsi64_si128(o as i64)), 
+        mfliph
+    );
+
+    let mut flip_l = _mm256_and_si256(moo, _mm256_sllv_epi64(pp, shift1897));
+    let mut flip_r = _mm256_and_si256(moo, _mm256_srlv_epi64(pp, shift1897));
+
+    flip_l = _mm256_or_si256(flip_l, _mm256_and_si256(moo, _mm256_sllv_epi64(flip_l, shift1897)));
+    flip_r = _mm256_or_si256(flip_r, _mm256_and_si256(moo, _mm256_srlv_epi64(flip_r, shift1897)));
+
+    let pre_l = _mm256_and_si256(moo, _mm256_sllv_epi64(moo, shift1897));
+    let pre_r = _mm256_srlv_epi64(pre_l, shift1897);
+
+    let shift2 = _mm256_add_epi64(shift1897, shift1897);
+
+    flip_l = _mm256_or_si256(flip_l, _mm256_and_si256(pre_l, _mm256_sllv_epi64(flip_l, shift2)));
+    flip_r = _mm256_or_si256(flip_r, _mm256_and_si256(pre_r, _mm256_srlv_epi64(flip_r, shift2)));
+
+    flip_l = _mm256_or_si256(flip_l, _mm256_and_si256(pre_l, _mm256_sllv_epi64(flip_l, shift2)));
+    flip_r = _mm256_or_si256(flip_r, _mm256_and_si256(pre_r, _mm256_srlv_epi64(flip_r, shift2)));
+
+    let mut mm = _mm256_sllv_epi64(flip_l, shift1897);
+    mm = _mm256_or_si256(mm, _mm256_srlv_epi64(flip_r, shift1897));
+
+    let m = _mm_or_si128(
+        _mm256_castsi256_si128(mm),
+        _mm256_extracti128_si256(mm, 1)
+    );
+
+    let m = _mm_or_si128(m, _mm_unpackhi_epi64(m, m));
+
+    (_mm_cvtsi128_si64(m) as u64) & !(p | o)
 }
 
 
-#[cfg(not(target_feature = "avx2"))]
 #[inline(always)]
-pub fn get_moves(p: u64, o: u64) -> u64 {
+pub fn get_moves_std(p: u64, o: u64) -> u64 {
 
     let mut moves: u64;
     
@@ -8,12 +8,17 @@ mod bit;
 pub mod cut_off;
 mod t_table;
 mod eval;
+mod eval_feature;
+mod eval_io;
+mod evaluate;
 mod mpc;
 mod human_book;
 mod count_last_flip;
 mod get_moves;
 mod flip;
 mod move_list;
+mod stability;
+mod cpu;
 // ---
 
 pub use board::*;
@@ -21,11 +26,16 @@ pub use eval_simple::*;
 pub use solver::*;
 pub use game::*;
 pub use eval::*;
+pub use eval_feature::{FeatureExtractor, FeatureBit, IncrementalFeatures};
+pub use eval_io::EvaluatorError;
+pub use evaluate::{Evaluate, IntoEvaluator, SimpleEvaluator};
 pub use t_table::*;
 pub use human_book::*;
 pub use cut_off::*;
 pub use mpc::{SELECTIVITY, SELECTIVITY_LV_MAX, N_SELECTIVITY_LV, NO_MPC};
+pub use mpc::{MpcParams, MpcTable, EvalSearchMpcModel, EVAL_SEARCH_MPC_MODEL, EVAL_SEARCH_MPC_SEARCH_LV, PERFECT_SEARCH_MPC_SEARCH_PARAMS, MPC_START_LEVEL_EVAL_SEARCH};
 pub use move_list::*;
+pub use cpu::{Backend, CpuFeatures, UnsupportedBackend, current_backend, set_backend, init_backend};
 
 
 #[cfg(test)]
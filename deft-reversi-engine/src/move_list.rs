@@ -50,7 +50,7 @@ impl MoveIteratorParity {
     const CORNER_MASK: u64 = 0x8100_0000_0000_0081;
 
     /// 4つの小盤面（左下・右下・左上・右上）に分割するマスク
-    const QUADRANT_MASKS: [u64; 4] = [
+    pub(crate) const QUADRANT_MASKS: [u64; 4] = [
         0x0000_0000_0f0f_0f0f,
         0x0000_0000_f0f0_f0f0,
         0xf0f0_f0f0_0000_0000,
@@ -1,3 +1,8 @@
+use std::fs;
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
 use crate::{
     board::*,
     cut_off::*,
@@ -70,11 +75,14 @@ pub const SELECTIVITY: [Selectivity;N_SELECTIVITY_LV] =
 
 ];
 
+/// 浅い探索の値 v から深い探索の値 V を V = a v + b ± e_std と予測するパラメータ
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct MpcParams {
+    /// 浅い探索の深さ
     pub lv    : i32,
-        a     : f64,
-        b     : f64,
-        e_std : f64
+    pub a     : f64,
+    pub b     : f64,
+    pub e_std : f64
 }
 
 pub const PERFECT_SEARCH_MPC_SEARCH_PARAMS: [Option<MpcParams>; 61] = [
@@ -144,7 +152,7 @@ pub const PERFECT_SEARCH_MPC_SEARCH_PARAMS: [Option<MpcParams>; 61] = [
 ];
 
 
-const MPC_START_LEVEL_EVAL_SEARCH: i32 = 4;
+pub const MPC_START_LEVEL_EVAL_SEARCH: i32 = 4;
 pub const EVAL_SEARCH_MPC_SEARCH_LV: [i32; 61] = [
     0,
     0, 0, 0, 0, 1, 2, 1, 2, 1, 2,
@@ -155,32 +163,111 @@ pub const EVAL_SEARCH_MPC_SEARCH_LV: [i32; 61] = [
     9, 10, 9, 10, 9, 10, 9, 10, 9, 10
 ];
 
-fn gen_eval_search_mpc_params(lv_i32: i32, n_empties: i32) -> MpcParams
-{
-    let mpc_lv_i32 = EVAL_SEARCH_MPC_SEARCH_LV[lv_i32 as usize];
-    let lv = lv_i32 as f64;
-    let mpc_lv = mpc_lv_i32 as f64;
-    let n_empties = n_empties as f64;
-
-    let a 
-        =  0.997868 
-        + -0.000399 * n_empties 
-        + -0.000590 * lv 
-        +  0.003595 * mpc_lv;
-
-    let b 
-        = -0.345286 
-        + -0.000993 * n_empties 
-        + -0.097065 * lv 
-        +  0.264205 * mpc_lv;
-
-    let e_std 
-        =  3.887029 
-        + -0.043874 * n_empties
-        +  0.323397 * lv 
-        + -0.609174 * mpc_lv;
-
-    MpcParams { lv: mpc_lv_i32, a, b, e_std}
+/// 評価関数による探索の MPC パラメータを、空きマス数・探索の深さ・浅い探索の深さの1次式で表す係数
+///
+/// a, b, e_std のそれぞれを `c[0] + c[1] * n_empties + c[2] * lv + c[3] * mpc_lv` で求める。
+/// (`mpc_lv` は `EVAL_SEARCH_MPC_SEARCH_LV[lv]`)
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EvalSearchMpcModel {
+    pub a     : [f64; 4],
+    pub b     : [f64; 4],
+    pub e_std : [f64; 4],
+}
+
+pub const EVAL_SEARCH_MPC_MODEL: EvalSearchMpcModel = EvalSearchMpcModel {
+    a     : [ 0.997868, -0.000399, -0.000590,  0.003595],
+    b     : [-0.345286, -0.000993, -0.097065,  0.264205],
+    e_std : [ 3.887029, -0.043874,  0.323397, -0.609174],
+};
+
+impl EvalSearchMpcModel {
+    pub fn params(&self, lv_i32: i32, n_empties: i32) -> MpcParams
+    {
+        let mpc_lv_i32 = EVAL_SEARCH_MPC_SEARCH_LV[lv_i32 as usize];
+        let x = [1.0, n_empties as f64, lv_i32 as f64, mpc_lv_i32 as f64];
+        let linear = |c: &[f64; 4]| c.iter().zip(&x).map(|(c, x)| c * x).sum::<f64>();
+
+        MpcParams { lv: mpc_lv_i32, a: linear(&self.a), b: linear(&self.b), e_std: linear(&self.e_std) }
+    }
+}
+
+/// MPC のパラメータ表
+///
+/// `Default` はコンパイル時の定数 (`EVAL_SEARCH_MPC_MODEL`, `PERFECT_SEARCH_MPC_SEARCH_PARAMS`) で、
+/// `deft-reversi-learn mpc` で求めた表を JSON ファイルから読み込んで差し替えられる。
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MpcTable {
+    pub eval_search    : EvalSearchMpcModel,
+    /// 完全読みのパラメータ (空きマス数ごと、61 個)
+    pub perfect_search : Vec<Option<MpcParams>>,
+}
+
+impl Default for MpcTable {
+    fn default() -> Self {
+        Self {
+            eval_search: EVAL_SEARCH_MPC_MODEL,
+            perfect_search: PERFECT_SEARCH_MPC_SEARCH_PARAMS.to_vec(),
+        }
+    }
+}
+
+impl MpcTable {
+    /// JSON ファイルから表を読み込む
+    ///
+    /// 読み込めない場合や探索に使えない表 (`validate`) の場合は、`path` を含むエラーを返す。
+    pub fn read_file(path: &str) -> io::Result<Self> {
+        let read = || -> io::Result<Self> {
+            let json = fs::read_to_string(path)?;
+            let table: Self = serde_json::from_str(&json)?;
+            table.validate().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            Ok(table)
+        };
+        read().map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))
+    }
+
+    pub fn write_file(&self, path: &str) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    /// 探索に使える表かどうか
+    ///
+    /// 浅い探索は元の探索より浅く、a と e_std は正の有限の値でなければならない。
+    /// (e_std が 0 以下だと、MPC が全てのノードで枝刈りしてしまう)
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.perfect_search.len() != 61 {
+            return Err("perfect_search must have 61 entries");
+        }
+        for (n_empties, params) in self.perfect_search.iter().enumerate() {
+            if let Some(p) = params {
+                if p.lv < 0 || p.lv >= n_empties as i32 || !p.is_valid() {
+                    return Err("invalid perfect_search parameters");
+                }
+            }
+        }
+        self.eval_search.validate()
+    }
+}
+
+impl MpcParams {
+    /// a, b, e_std が有限の値で、a と e_std が正かどうか
+    pub fn is_valid(&self) -> bool {
+        self.a.is_finite() && self.b.is_finite() && self.e_std.is_finite() && self.a > 0.0 && self.e_std > 0.0
+    }
+}
+
+impl EvalSearchMpcModel {
+    /// MPC を使う全ての深さ (`MPC_START_LEVEL_EVAL_SEARCH..=60`) と空きマス数 (`0..=60`) で、
+    /// 求めたパラメータが正しい値になるかどうか
+    pub fn validate(&self) -> Result<(), &'static str> {
+        for lv in MPC_START_LEVEL_EVAL_SEARCH..=60 {
+            for n_empties in 0..=60 {
+                if !self.params(lv, n_empties).is_valid() {
+                    return Err("eval_search gives invalid parameters for some depth and number of empties");
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 #[inline(always)]
@@ -196,7 +283,7 @@ pub fn eval_search_mpc(
         return  ProbCutResult::Fail;
     }
     let n_empties = board.empties_count();
-    let mpc_params = gen_eval_search_mpc_params(lv, n_empties);
+    let mpc_params = search.mpc_table.eval_search.params(lv, n_empties);
 
     multi_prob_cut(board, alpha, beta, &mpc_params, search)
 }
@@ -210,11 +297,11 @@ pub fn perfect_search_mpc(
 ) -> ProbCutResult
 {
     let n_empties = board.empties_count();
-    let mpc_params = match &PERFECT_SEARCH_MPC_SEARCH_PARAMS[n_empties as usize] {
+    let mpc_params = match search.mpc_table.perfect_search[n_empties as usize] {
         Some(params) => { params },
         None                     => { return ProbCutResult::Fail }
     };
-    multi_prob_cut(board, alpha, beta, mpc_params, search)
+    multi_prob_cut(board, alpha, beta, &mpc_params, search)
 }
 
 #[inline(always)]
@@ -279,4 +366,72 @@ fn nws_eval_0_selectivity_lv(board: &Board, alpha: i32, lv: i32, search : &mut S
     let score = nws_eval(board, alpha, lv, search);
     search.selectivity_lv = main_search_selectivity_lv;
     score
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mpc_table_roundtrip() {
+        let table = MpcTable::default();
+        assert!(table.validate().is_ok());
+        assert_eq!(table.perfect_search.len(), 61);
+
+        let json = serde_json::to_string(&table).unwrap();
+        let restored: MpcTable = serde_json::from_str(&json).unwrap();
+        // serde_json の浮動小数点数の読み込みは、最後の桁が一致しないことがある
+        assert_eq!(restored.eval_search, table.eval_search);
+        for (r, t) in restored.perfect_search.iter().zip(&table.perfect_search) {
+            match (r, t) {
+                (Some(r), Some(t)) => {
+                    assert_eq!(r.lv, t.lv);
+                    assert!((r.a - t.a).abs() < 1e-12 && (r.b - t.b).abs() < 1e-12 && (r.e_std - t.e_std).abs() < 1e-12);
+                }
+                (r, t) => assert_eq!(r.is_some(), t.is_some()),
+            }
+        }
+
+        let params = table.eval_search.params(10, 30);
+        assert_eq!(params.lv, EVAL_SEARCH_MPC_SEARCH_LV[10]);
+        assert!((params.a - (0.997868 - 0.000399 * 30.0 - 0.000590 * 10.0 + 0.003595 * 2.0)).abs() < 1e-12);
+
+        let mut invalid = table.clone();
+        invalid.perfect_search[12] = Some(MpcParams { lv: 12, a: 1.0, b: 0.0, e_std: 5.0 });
+        assert!(invalid.validate().is_err());
+        invalid.perfect_search.pop();
+        assert!(invalid.validate().is_err());
+
+        for e_std in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let mut invalid = table.clone();
+            invalid.perfect_search[12] = Some(MpcParams { lv: 4, a: 1.0, b: 0.0, e_std });
+            assert!(invalid.validate().is_err());
+        }
+
+        // 空きマス数が多いと e_std が負になる式
+        let mut invalid = table.clone();
+        invalid.eval_search.e_std[1] = -0.1;
+        assert!(invalid.validate().is_err());
+        let mut invalid = table.clone();
+        invalid.eval_search.a = [f64::NAN, 0.0, 0.0, 0.0];
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn read_file_reports_the_path_once() {
+        let path = std::env::temp_dir().join("deft_reversi_mpc_table_invalid.json");
+        let path = path.to_str().unwrap();
+        let mut table = MpcTable::default();
+        table.perfect_search.pop();
+        table.write_file(path).unwrap();
+
+        let e = MpcTable::read_file(path).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(e.to_string(), format!("{}: perfect_search must have 61 entries", path));
+        std::fs::remove_file(path).unwrap();
+
+        let e = MpcTable::read_file(path).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::NotFound);
+        assert!(e.to_string().starts_with(&format!("{}: ", path)));
+    }
+}
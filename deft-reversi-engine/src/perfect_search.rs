@@ -21,6 +21,13 @@ const SWITCH_EMPTIES_SIMPLE_NWS: i32 = 10;
 /// `pvs_perfect_simple`や`nws_perfect_simple`から、`negaalpha_perfect`へ切り替える
 const SWITCH_EMPTIES_NEGA_ALPHA: i32 = 5;
 
+/// 探索を始めた局面 (`origin_board`) の空きマスが`STABILITY_CUT_OFF_MIN_EMPTIES`以上である場合のみ、
+/// 確定石による枝刈りを行う
+///
+/// 確定石を数える分だけ各ノードの探索が遅くなるため、探索全体が小さい場合は、減らせるノード数より時間がかかる。
+/// (data/log/ffo_test/stability_20261018.txt)
+const STABILITY_CUT_OFF_MIN_EMPTIES: i32 = 24;
+
 
 const MC: u64 = 0b1000000100000000000000000000000000000000000000000000000010000001_u64;
 const MX: u64 = 0b0000000001000010000000000000000000000000000000000100001000000000_u64;
@@ -115,6 +122,9 @@ pub fn solve_score_1_empties(board_player: u64, alpha: i32, x: usize) -> i32 {
         let n_flips = count_last_flip(x, !board_player);
         if n_flips != 0 {
             score -= n_flips + 2;
+        } else if score <= 0 {
+            // 両者とも打てない場合、空きマスは勝っている側のものになる
+            score -= 2;
         }
     }
     // * use lazy cut-off :
@@ -266,6 +276,114 @@ pub fn solve_score_2_empties(board: &Board, alpha: i32, beta: i32, search: &mut
     score
 }
 
+/// 空きマスを、空きマスが奇数個の小盤面にあるものから順に並べる
+#[inline(always)]
+fn sort_empties_by_parity<const N: usize>(empties: u64) -> [u64; N] {
+    let mut odd_mask = 0;
+    for mask in MoveIteratorParity::QUADRANT_MASKS {
+        if (empties & mask).count_ones() % 2 == 1 {
+            odd_mask |= mask;
+        }
+    }
+
+    let mut sorted = [0u64; N];
+    let mut i = 0;
+    for mut squares in [empties & odd_mask, empties & !odd_mask] {
+        while squares != 0 {
+            sorted[i] = squares & (!squares + 1);
+            squares &= squares - 1;
+            i += 1;
+        }
+    }
+    sorted
+}
+
+/// 空きマスが残り3のときの完全読み
+///
+/// 盤面を複製せずに着手し、空きマスが奇数個の小盤面から先に打つ。
+pub fn solve_score_3_empties(board: &Board, mut alpha: i32, beta: i32, search: &mut SearchEngine) -> i32 {
+    search.status.perfect_search_node_count += 1;
+    let empties = !(board.player | board.opponent);
+
+    let mut best_score = -SCORE_INF;
+    for x in sort_empties_by_parity::<3>(empties) {
+        if (NEIGHBOUR[x.trailing_zeros() as usize] & board.opponent) == 0 {
+            continue;
+        }
+        let flip = board.flip_bit(x);
+        if flip == 0 {
+            continue;
+        }
+        let next = Board {
+            player: board.opponent ^ flip,
+            opponent: board.player ^ flip ^ x,
+        };
+        let score = -solve_score_2_empties(&next, -beta, -alpha, search);
+        if score >= beta {
+            return score;
+        }
+        if score > alpha {
+            alpha = score;
+        }
+        if score > best_score {
+            best_score = score;
+        }
+    }
+
+    // 合法手がない
+    if best_score == -SCORE_INF {
+        if board.opponent_moves() == 0 {
+            search.status.perfect_search_leaf_node_count += 1;
+            return solve_score(board);
+        }
+        return -solve_score_3_empties(&board.swapped_board(), -beta, -alpha, search);
+    }
+    best_score
+}
+
+/// 空きマスが残り4のときの完全読み
+///
+/// 盤面を複製せずに着手し、空きマスが奇数個の小盤面から先に打つ。
+pub fn solve_score_4_empties(board: &Board, mut alpha: i32, beta: i32, search: &mut SearchEngine) -> i32 {
+    search.status.perfect_search_node_count += 1;
+    let empties = !(board.player | board.opponent);
+
+    let mut best_score = -SCORE_INF;
+    for x in sort_empties_by_parity::<4>(empties) {
+        if (NEIGHBOUR[x.trailing_zeros() as usize] & board.opponent) == 0 {
+            continue;
+        }
+        let flip = board.flip_bit(x);
+        if flip == 0 {
+            continue;
+        }
+        let next = Board {
+            player: board.opponent ^ flip,
+            opponent: board.player ^ flip ^ x,
+        };
+        let score = -solve_score_3_empties(&next, -beta, -alpha, search);
+        if score >= beta {
+            return score;
+        }
+        if score > alpha {
+            alpha = score;
+        }
+        if score > best_score {
+            best_score = score;
+        }
+    }
+
+    // 合法手がない
+    if best_score == -SCORE_INF {
+        if board.opponent_moves() == 0 {
+            search.status.perfect_search_leaf_node_count += 1;
+            return solve_score(board);
+        }
+        return -solve_score_4_empties(&board.swapped_board(), -beta, -alpha, search);
+    }
+    best_score
+}
+
 /// NegaAlpha法を用いて、完全読みを行い、オセロの盤面のスコアを計算する。
 ///
 /// 探索速度を向上させるため、葉に近いノードで使用される。
@@ -284,13 +402,15 @@ pub fn negaalpha_perfect(board: &Board, mut alpha: i32, beta: i32, search: &mut
     #[cfg(debug_assertions)]
     assert!(alpha <= beta);
 
-    search.status.perfect_search_node_count += 1;
+    // 空きマスが残り2~4のときは、専用の関数を用いる
+    match board.empties_count() {
+        4 => return solve_score_4_empties(board, alpha, beta, search),
+        3 => return solve_score_3_empties(board, alpha, beta, search),
+        2 => return solve_score_2_empties(board, alpha, beta, search),
+        _ => (),
+    }
 
-    // 空きマスが残り2のとき
-    let n_empties = board.empties_count();
-    // if (board.player | board.opponent).count_zeros() == 2 {
-    //     return solve_score_2_empties(board, alpha, beta, search);
-    // }
+    search.status.perfect_search_node_count += 1;
 
     let legal_moves = board.moves();
 
@@ -317,11 +437,7 @@ pub fn negaalpha_perfect(board: &Board, mut alpha: i32, beta: i32, search: &mut
     for legal_move in move_iter {
         let mut current_board = board.clone();
         current_board.put_piece_fast(legal_move);
-        let score: i32 = if n_empties - 1 == 2 {
-            -solve_score_2_empties(&current_board, -beta, -alpha, search)
-        } else {
-            -negaalpha_perfect(&current_board, -beta, -alpha, search)
-        };
+        let score: i32 = -negaalpha_perfect(&current_board, -beta, -alpha, search);
         if score >= beta {
             return score;
         }
@@ -351,7 +467,7 @@ pub fn negaalpha_perfect(board: &Board, mut alpha: i32, beta: i32, search: &mut
 /// * 終盤の局面では、`negaalpha_perfect` 関数に切り替わります。
 pub fn nws_perfect_simple(board: &Board, mut alpha: i32, search: &mut SearchEngine) -> i32 {
     // 探索範囲: [alpha, beta]
-    let beta: i32 = alpha + 1;
+    let mut beta: i32 = alpha + 1;
 
     let n_empties = board.empties_count();
     if n_empties < SWITCH_EMPTIES_NEGA_ALPHA {
@@ -360,6 +476,13 @@ pub fn nws_perfect_simple(board: &Board, mut alpha: i32, search: &mut SearchEngi
 
     search.status.perfect_search_node_count += 1;
 
+    // Stability Cut off
+    if search.origin_board.empties_count() >= STABILITY_CUT_OFF_MIN_EMPTIES {
+        if let Some(score) = stability_cut_off(board, &mut alpha, &mut beta) {
+            return score;
+        }
+    }
+
     let moves_bit: u64 = board.moves();
 
     if moves_bit == 0 {
@@ -530,8 +653,20 @@ pub fn nws_perfect(board: &Board, mut alpha: i32, search: &mut SearchEngine) ->
         return nws_perfect_simple(board, alpha, search);
     }
 
+    // 打ち切られた探索の結果は使われないため、任意の値を返してよい
+    if search.check_limits() {
+        return 0;
+    }
+
     search.status.perfect_search_node_count += 1;
 
+    // Stability Cut off
+    if search.origin_board.empties_count() >= STABILITY_CUT_OFF_MIN_EMPTIES {
+        if let Some(score) = stability_cut_off(board, &mut alpha, &mut beta) {
+            return score;
+        }
+    }
+
     // 探索範囲: [alpha, beta]
     let mut moves_bit: u64 = board.moves();
 
@@ -630,7 +765,7 @@ pub fn nws_perfect(board: &Board, mut alpha: i32, search: &mut SearchEngine) ->
             }
             let score: i32 = -nws_perfect(&move_board.board, -beta, search);
             if score >= beta {
-                search.t_table.add(
+                search.t_table_add(
                     board,
                     score,
                     SCORE_INF,
@@ -679,7 +814,7 @@ pub fn nws_perfect(board: &Board, mut alpha: i32, search: &mut SearchEngine) ->
                 }
                 // let node_count_tmp = search.status.eval_search_node_count;
                 let search_eval = match lv - 1 {
-                    0 => -search.eval_func.clac_features_eval(&move_board.board),
+                    0 => -search.eval_func.evaluate(&move_board.board, &mut search.eval_scratch),
                     1 | 2 => -negaalpha_eval_no_mo(&move_board.board, alpha, beta, lv - 1, search),
                     3..=60 => -negaalpha_eval(&move_board.board, alpha, beta, lv - 1, search),
                     _ => 0
@@ -706,7 +841,7 @@ pub fn nws_perfect(board: &Board, mut alpha: i32, search: &mut SearchEngine) ->
         }
         let score: i32 = -nws_perfect(&move_board.board, -beta, search);
         if score >= beta {
-            search.t_table.add(
+            search.t_table_add(
                 board,
                 score,
                 SCORE_INF,
@@ -729,7 +864,7 @@ pub fn nws_perfect(board: &Board, mut alpha: i32, search: &mut SearchEngine) ->
     }
 
     if best_score > alpha {
-        search.t_table.add(
+        search.t_table_add(
             board,
             best_score,
             best_score,
@@ -738,7 +873,7 @@ pub fn nws_perfect(board: &Board, mut alpha: i32, search: &mut SearchEngine) ->
             best_move,
         );
     } else {
-        search.t_table.add(
+        search.t_table_add(
             board,
             -SCORE_INF,
             best_score,
@@ -775,8 +910,20 @@ pub fn pvs_perfect(board: &Board, mut alpha: i32, mut beta: i32, search: &mut Se
     #[cfg(debug_assertions)]
     assert!(alpha <= beta);
 
+    // 打ち切られた探索の結果は使われないため、任意の値を返してよい
+    if search.check_limits() {
+        return 0;
+    }
+
     search.status.perfect_search_node_count += 1;
 
+    // Stability Cut off
+    if search.origin_board.empties_count() >= STABILITY_CUT_OFF_MIN_EMPTIES {
+        if let Some(score) = stability_cut_off(board, &mut alpha, &mut beta) {
+            return score;
+        }
+    }
+
     // 探索範囲: [alpha, beta]
     let mut moves_bit: u64 = board.moves();
 
@@ -883,7 +1030,7 @@ pub fn pvs_perfect(board: &Board, mut alpha: i32, mut beta: i32, search: &mut Se
             if !pvs_ok {
                 let score: i32 = -pvs_perfect(&move_board.board, -beta, -alpha, search);
                 if score >= beta {
-                    search.t_table.add(
+                    search.t_table_add(
                         board,
                         score,
                         SCORE_INF,
@@ -904,7 +1051,7 @@ pub fn pvs_perfect(board: &Board, mut alpha: i32, mut beta: i32, search: &mut Se
             } else {
                 let mut score: i32 = -nws_perfect(&move_board.board, -this_node_alpha - 1, search);
                 if score >= beta {
-                    search.t_table.add(
+                    search.t_table_add(
                         board,
                         score,
                         SCORE_INF,
@@ -914,11 +1061,11 @@ pub fn pvs_perfect(board: &Board, mut alpha: i32, mut beta: i32, search: &mut Se
                     );
                     return score;
                 }
-                if score > best_score {
+                if score > this_node_alpha {
                     // 再探索
                     score = -pvs_perfect(&move_board.board, -beta, -this_node_alpha, search);
                     if score >= beta {
-                        search.t_table.add(
+                        search.t_table_add(
                             board,
                             score,
                             SCORE_INF,
@@ -928,12 +1075,14 @@ pub fn pvs_perfect(board: &Board, mut alpha: i32, mut beta: i32, search: &mut Se
                         );
                         return score;
                     }
-                    best_move = move_board.put_place;
-                    best_score = score;
                     if score > this_node_alpha {
                         this_node_alpha = score;
                     }
                 }
+                if score > best_score {
+                    best_move = move_board.put_place;
+                    best_score = score;
+                }
             }
         }
     }
@@ -993,7 +1142,7 @@ pub fn pvs_perfect(board: &Board, mut alpha: i32, mut beta: i32, search: &mut Se
             best_move = move_board.put_place;
             best_score = -pvs_perfect(&move_board.board, -beta, -this_node_alpha, search);
             if best_score >= beta {
-                search.t_table.add(
+                search.t_table_add(
                     board,
                     best_score,
                     SCORE_INF,
@@ -1016,7 +1165,7 @@ pub fn pvs_perfect(board: &Board, mut alpha: i32, mut beta: i32, search: &mut Se
         }
         let mut score: i32 = -nws_perfect(&move_board.board, -this_node_alpha - 1, search);
         if score >= beta {
-            search.t_table.add(
+            search.t_table_add(
                 board,
                 score,
                 SCORE_INF,
@@ -1026,11 +1175,11 @@ pub fn pvs_perfect(board: &Board, mut alpha: i32, mut beta: i32, search: &mut Se
             );
             return score;
         }
-        if score > best_score {
+        if score > this_node_alpha {
             // 再探索
             score = -pvs_perfect(&move_board.board, -beta, -this_node_alpha, search);
             if score >= beta {
-                search.t_table.add(
+                search.t_table_add(
                     board,
                     score,
                     SCORE_INF,
@@ -1040,12 +1189,14 @@ pub fn pvs_perfect(board: &Board, mut alpha: i32, mut beta: i32, search: &mut Se
                 );
                 return score;
             }
-            best_move = move_board.put_place;
-            best_score = score;
             if score > this_node_alpha {
                 this_node_alpha = score;
             }
         }
+        if score > best_score {
+            best_move = move_board.put_place;
+            best_score = score;
+        }
     }
 
     if best_move == NO_COORD {
@@ -1053,7 +1204,7 @@ pub fn pvs_perfect(board: &Board, mut alpha: i32, mut beta: i32, search: &mut Se
     }
     if best_score > alpha {
         // alpha < best_score < beta
-        search.t_table.add(
+        search.t_table_add(
             board,
             best_score,
             best_score,
@@ -1063,7 +1214,7 @@ pub fn pvs_perfect(board: &Board, mut alpha: i32, mut beta: i32, search: &mut Se
         );
     } else {
         // best_score <= alpha
-        search.t_table.add(
+        search.t_table_add(
             board,
             -SCORE_INF,
             best_score,
@@ -1075,3 +1226,94 @@ pub fn pvs_perfect(board: &Board, mut alpha: i32, mut beta: i32, search: &mut Se
 
     best_score
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::Evaluator;
+    use crate::eval_simple::put_random_piece;
+
+    /// 全探索による完全読み
+    fn brute_force(board: &Board) -> i32 {
+        let moves = board.moves();
+        if moves == 0 {
+            if board.opponent_moves() == 0 {
+                return solve_score(board);
+            }
+            return -brute_force(&board.swapped_board());
+        }
+        let mut best_score = -SCORE_INF;
+        for x in MoveIterator::new(moves) {
+            let mut next = board.clone();
+            next.put_piece_fast(x);
+            best_score = best_score.max(-brute_force(&next));
+        }
+        best_score
+    }
+
+    /// 空きマスが `n_empties` 個になるまでランダムに打ち進める
+    fn random_board(n_empties: i32) -> Option<Board> {
+        let mut board = Board::new();
+        while board.empties_count() > n_empties {
+            if put_random_piece(&mut board).is_err() {
+                board.swap();
+                if board.moves() == 0 {
+                    return None;
+                }
+            }
+        }
+        Some(board)
+    }
+
+    #[test]
+    fn small_empties_solvers_match_brute_force() {
+        let mut search = SearchEngine::new(Evaluator::zero());
+        let windows = [(-SCORE_INF, SCORE_INF), (-1, 1), (-9, -7), (3, 5), (10, 30), (-40, -20)];
+
+        for n_empties in 1..=5 {
+            let mut n_boards = 0;
+            while n_boards < 300 {
+                let Some(board) = random_board(n_empties) else {
+                    continue;
+                };
+                n_boards += 1;
+                let expected = brute_force(&board);
+                for (alpha, beta) in windows {
+                    let score = negaalpha_perfect(&board, alpha, beta, &mut search);
+                    if expected <= alpha {
+                        assert!(expected <= score && score <= alpha, "{:#x} {:#x}", board.player, board.opponent);
+                    } else if expected >= beta {
+                        assert!(beta <= score && score <= expected, "{:#x} {:#x}", board.player, board.opponent);
+                    } else {
+                        assert_eq!(score, expected, "{:#x} {:#x}", board.player, board.opponent);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn last_empty_square_goes_to_the_winner() {
+        // A1 だけが空いていて、どちらも打てない局面
+        let a1 = 0;
+        let all_but_a1 = !1u64;
+
+        // 手番側が全ての石を持つ: 63 - 0 に空きマスを加えて +64
+        assert_eq!(solve_score_1_empties(all_but_a1, -SCORE_INF, a1), 64);
+        // 相手が全ての石を持つ: 空きマスは相手のものになり -64
+        assert_eq!(solve_score_1_empties(0, -SCORE_INF, a1), -64);
+    }
+
+    #[test]
+    fn re_search_keeps_the_best_score() {
+        // fforum-1-19 #1 の探索中に現れる空きマス9の局面 (正しい値は -18)
+        // ヌルウィンドウ探索で alpha 以下 (上限) の値が返った手も再探索し、その値で最善値 -18 が -20 に上書きされていた
+        let board = Board { player: 0x00f0_e9b1_80cc_8a00, opponent: 0x7f0e_144e_7f32_347c };
+        let mut search = SearchEngine::new(Evaluator::zero());
+        search.selectivity_lv = NO_MPC;
+
+        let score = pvs_perfect(&board, -17, -13, &mut search);
+        assert!((-18..=-17).contains(&score), "{}", score);
+        assert_eq!(pvs_perfect(&board, -SCORE_INF, SCORE_INF, &mut search), -18);
+    }
+}
@@ -1,13 +1,19 @@
-use crate::eval::Evaluator;
+use crate::eval::FeatureScratch;
+use crate::evaluate::*;
 use crate::{eval_search::*, perfect_search};
-use crate::evaluator_const::SCORE_MAX;
-use crate::mpc::{Selectivity, NO_MPC, N_SELECTIVITY_LV, SELECTIVITY, SELECTIVITY_LV_MAX};
+use crate::evaluator_const::{SCORE_INF, SCORE_MAX};
+use crate::mpc::{MpcTable, Selectivity, NO_MPC, N_SELECTIVITY_LV, SELECTIVITY, SELECTIVITY_LV_MAX};
 use crate::perfect_search::*;
 use crate::{board::*, TranspositionTable};
 use crate::move_list::*;
+use crate::cpu::init_backend;
+use crate::stability::init_edge_stable_table;
 
 use std::cmp;
 use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 const AI_LEVEL_MAX: usize = 60;
 
@@ -15,6 +21,9 @@ const AI_LEVEL_MAX: usize = 60;
 pub enum SolverType {
     Eval(i32, i32), // depth, selectivity_lv
     Perfect(i32),  // selectivity_lv
+    /// 勝敗 (勝ち・引き分け・負け) のみを求める完全読み
+    /// 評価値は勝ち +1, 引き分け 0, 負け -1 となる。
+    Wld(i32), // selectivity_lv
 }
 
 impl SolverType {
@@ -29,18 +38,83 @@ impl SolverType {
                 "Eval solver (Lv.{}, {}%)",
                 lv, SELECTIVITY[selectivity_lv as usize].percent
             ),
+            SolverType::Wld(selectivity_lv) => format!(
+                "WLD solver ({}%)",
+                SELECTIVITY[selectivity_lv as usize].percent
+            ),
+        }
+    }
+
+    pub fn selectivity_lv(&self) -> i32 {
+        match *self {
+            SolverType::Eval(_, selectivity_lv)
+            | SolverType::Perfect(selectivity_lv)
+            | SolverType::Wld(selectivity_lv) => selectivity_lv,
+        }
+    }
+
+    /// 探索する深さ (完全読みの場合は空きマス数)
+    pub fn depth(&self, board: &Board) -> i32 {
+        match *self {
+            SolverType::Eval(lv, _) => lv,
+            SolverType::Perfect(_) | SolverType::Wld(_) => board.empties_count(),
         }
     }
 }
 
 const EVAL_SOLVER_SELECTIVITY: i32 = 1;
 
+/// 並列探索を行う最小の探索レベル (評価関数による探索)
+/// これより浅い探索では、スレッドの起動コストの方が大きくなるため逐次探索を行う。
+const PARALLEL_SEARCH_MIN_EVAL_LV: i32 = 8;
+
+/// 並列探索を行う最小の空きマス数 (完全読み)
+const PARALLEL_SEARCH_MIN_EMPTIES: i32 = 12;
+
+/// 探索の打ち切り条件を確認する間隔 (探索したノードの数)
+const LIMIT_CHECK_INTERVAL: u64 = 1024;
+
+/// 探索の打ち切り条件
+///
+/// いずれかの条件を満たすと探索を打ち切り、`Solver::solve_with_limits` は
+/// 最後に完了した反復の結果を返す。
+/// 並列探索時のノード数は、スレッドごとに判定する。
+#[derive(Clone, Default)]
+pub struct SearchLimits {
+    /// 探索の期限
+    pub deadline: Option<Instant>,
+    /// 探索するノード数の上限
+    pub max_nodes: Option<u64>,
+    /// 外部から探索を停止するためのフラグ
+    pub stop: Option<Arc<AtomicBool>>,
+}
+
+impl SearchLimits {
+    fn is_exceeded(&self, n_nodes: u64) -> bool {
+        self.max_nodes.is_some_and(|max_nodes| n_nodes >= max_nodes)
+            || self.stop.as_ref().is_some_and(|stop| stop.load(Ordering::Relaxed))
+            // wasm では Instant::now() が使えないため、期限が設定されている場合のみ呼び出す
+            || self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
 pub struct SearchEngine {
-    pub t_table: TranspositionTable,
+    pub t_table: Arc<TranspositionTable>,
     pub origin_board: Board,
-    pub eval_func: Evaluator,
+    /// 評価関数 (補助エンジンや他の `Solver` と共有できる)
+    pub eval_func: Arc<dyn Evaluate>,
+    /// 評価時に特徴を計算するための作業領域
+    pub eval_scratch: FeatureScratch,
     pub selectivity_lv: i32,
-    pub status: SearchStats
+    /// MPC のパラメータ (補助エンジンと共有する)
+    pub mpc_table: Arc<MpcTable>,
+    pub status: SearchStats,
+    pub limits: SearchLimits,
+    /// 補助エンジンと共有する打ち切りフラグ
+    abort: Arc<AtomicBool>,
+    is_aborted: bool,
+    /// 最後に打ち切り条件を確認した時点のノード数
+    n_nodes_at_limit_check: u64,
 }
 
 #[derive(Default)]
@@ -60,18 +134,65 @@ impl SearchStats {
         self.perfect_search_node_count = 0;
         self.perfect_search_leaf_node_count = 0;
     }
+
+    /// 他のエンジンで探索したノード数を加算する
+    fn merge(&mut self, other: &SearchStats) {
+        self.eval_search_node_count += other.eval_search_node_count;
+        self.eval_search_leaf_node_count += other.eval_search_leaf_node_count;
+        self.perfect_search_node_count += other.perfect_search_node_count;
+        self.perfect_search_leaf_node_count += other.perfect_search_leaf_node_count;
+    }
 }
 
 impl SearchEngine {
-    pub fn new(evaluator: Evaluator) -> SearchEngine {
+    pub fn new(evaluator: impl IntoEvaluator) -> SearchEngine {
+        Self::with_t_table(evaluator, TranspositionTable::new())
+    }
+
+    /// 置換表のメモリ使用量 (MB) を指定して生成する
+    pub fn with_t_table_size_mb(evaluator: impl IntoEvaluator, size_mb: usize) -> SearchEngine {
+        Self::with_t_table(evaluator, TranspositionTable::with_size_mb(size_mb))
+    }
+
+    fn with_t_table(evaluator: impl IntoEvaluator, t_table: TranspositionTable) -> SearchEngine {
+        // 着手可能位置の計算・石の反転の実装を、実行中のCPUに合わせて選択する
+        init_backend();
+        // 完全読みの確定石の計算に使うテーブルを、探索の前に作っておく
+        init_edge_stable_table();
         SearchEngine {
-            t_table: TranspositionTable::new(),
+            t_table: Arc::new(t_table),
             origin_board: Board::new(),
-            eval_func: evaluator,
+            eval_func: evaluator.into_evaluator(),
+            eval_scratch: FeatureScratch::default(),
             selectivity_lv: NO_MPC,
-            status: SearchStats::default()
+            mpc_table: Arc::new(MpcTable::default()),
+            status: SearchStats::default(),
+            limits: SearchLimits::default(),
+            abort: Arc::new(AtomicBool::new(false)),
+            is_aborted: false,
+            n_nodes_at_limit_check: 0,
+        }
+    }
+
+    /// 並列探索用の補助エンジンを生成する
+    ///
+    /// 置換表と評価関数は共有し、特徴の作業領域のみ複製する。
+    fn new_helper(&self) -> SearchEngine {
+        SearchEngine {
+            t_table: Arc::clone(&self.t_table),
+            origin_board: self.origin_board.clone(),
+            eval_func: Arc::clone(&self.eval_func),
+            eval_scratch: self.eval_scratch.clone(),
+            selectivity_lv: self.selectivity_lv,
+            mpc_table: Arc::clone(&self.mpc_table),
+            status: SearchStats::default(),
+            limits: self.limits.clone(),
+            abort: Arc::clone(&self.abort),
+            is_aborted: false,
+            n_nodes_at_limit_check: 0,
         }
     }
+
     pub fn clear_node_count(&mut self) {
         self.status.clear();
     }
@@ -80,6 +201,63 @@ impl SearchEngine {
         self.origin_board = board.clone();
         self.clear_node_count();
     }
+
+    /// 探索が打ち切られたかどうか
+    pub fn is_aborted(&self) -> bool {
+        self.is_aborted
+    }
+
+    /// 探索の打ち切り条件を確認する
+    ///
+    /// 条件の判定は、`LIMIT_CHECK_INTERVAL` ノードを探索するごとに 1 回だけ行う。
+    /// 置換表を使わない終盤の探索のノードも数えるため、ノード数の上限を大きく超えることはない。
+    /// いずれかのスレッドで打ち切られた場合、他のスレッドの探索も打ち切られる。
+    #[inline(always)]
+    pub fn check_limits(&mut self) -> bool {
+        if self.is_aborted {
+            return true;
+        }
+        let n_nodes = self.status.eval_search_node_count + self.status.perfect_search_node_count;
+        // ノード数が数え直された (`status.clear()`) 場合も、差が開けば判定する
+        if n_nodes.abs_diff(self.n_nodes_at_limit_check) < LIMIT_CHECK_INTERVAL {
+            return false;
+        }
+        self.check_limits_now()
+    }
+
+    fn check_limits_now(&mut self) -> bool {
+        let n_nodes = self.status.eval_search_node_count + self.status.perfect_search_node_count;
+        self.n_nodes_at_limit_check = n_nodes;
+        if self.limits.is_exceeded(n_nodes) {
+            self.abort.store(true, Ordering::Relaxed);
+        }
+        self.is_aborted = self.abort.load(Ordering::Relaxed);
+        self.is_aborted
+    }
+
+    fn reset_abort(&mut self) {
+        self.abort.store(false, Ordering::Relaxed);
+        self.is_aborted = false;
+        self.n_nodes_at_limit_check = 0;
+    }
+
+    /// 置換表に登録する
+    ///
+    /// 打ち切られた後の探索結果は正しくないため、登録しない。
+    #[inline(always)]
+    pub fn t_table_add(
+        &self,
+        board: &Board,
+        min: i32,
+        max: i32,
+        lv: i32,
+        selectivity_lv: i32,
+        best_move: u8,
+    ) {
+        if !self.is_aborted {
+            self.t_table.add(board, min, max, lv, selectivity_lv, best_move);
+        }
+    }
 }
 
 pub struct PutBoard {
@@ -105,32 +283,281 @@ pub struct SolverResult {
     pub best_move: u64,
     pub eval: i32,
     pub solver_type: SolverType,
+    /// 探索したノード数
+    ///
+    /// 最善応手列 `pv` の復元で確かめた探索のノード数も含む。
     pub searched_nodes: u64,
     pub searched_leaf_nodes: u64,
+    /// 探索の打ち切り条件により、探索が途中で打ち切られたかどうか
+    pub is_aborted: bool,
+    /// 最善応手列 (principal variation)
+    ///
+    /// 先頭は `best_move` で、パスは `PASS` で表す。
+    /// ただし、手番側に合法手がなくパスする局面では、先頭が `PASS` で、
+    /// 2番目が `best_move` (パスした後の相手の最善手) になる。
+    pub pv: Vec<u8>,
+    /// 最善応手列が終局または探索の深さに達する前に途切れたかどうか
+    ///
+    /// 置換表から評価値を実現する手を見つけられなかった局面や、
+    /// 打ち切り条件 (`SearchLimits`) により手を確かめる探索が打ち切られた局面で途切れる。
+    pub is_pv_truncated: bool,
 }
 
+impl SolverResult {
+    /// 最善応手列を "E6 F4 C3" の形式の文字列にする
+    ///
+    /// 最善応手列が途切れた場合は、末尾に "..." を付ける。
+    pub fn pv_string(&self) -> String {
+        let mut moves = self.pv
+            .iter()
+            .map(|&m| {
+                if m == PASS {
+                    "pass".to_string()
+                } else {
+                    position_bit_to_str(1 << m).unwrap()
+                }
+            })
+            .collect::<Vec<_>>();
+        if self.is_pv_truncated {
+            moves.push("...".to_string());
+        }
+        moves.join(" ")
+    }
+}
+
+/// 評価値の種類
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScoreBound {
+    /// 正確な評価値
+    Exact,
+    /// 評価値の上限 (実際の評価値はこれ以下)
+    Upper,
+    /// 評価値の下限 (実際の評価値はこれ以上)
+    Lower,
+}
+
+/// `Solver::solve_multi_pv` で求めた、ルートの各合法手の評価値
+#[derive(Clone, Copy, Debug)]
+pub struct MoveScore {
+    pub put_place: u8,
+    pub score: i32,
+    pub bound: ScoreBound,
+}
+
+/// `Solver::check_move` の結果
+pub struct MoveCheckResult {
+    /// 手の評価値が閾値以上であることが示されたかどうか
+    pub is_at_least: bool,
+    /// 示された評価値の下限 (`is_at_least` が `true` の場合) または上限
+    pub score: i32,
+    pub bound: ScoreBound,
+    /// MPC を使わない完全読みで示された (証明された) 限界かどうか
+    ///
+    /// `false` の場合、評価関数による探索や MPC による推定であり、限界が正しいとは限らない。
+    pub is_proven: bool,
+    pub solver_type: SolverType,
+    pub searched_nodes: u64,
+}
+
+/// 反復深化で完了した反復の結果
+struct IterationResult {
+    best_move: u8,
+    eval: i32,
+    solver_type: SolverType,
+}
+
+
+/// 並列探索 (YBWC) で分割したノードの、スレッド間で共有する探索状態
+struct SplitPoint {
+    alpha: i32,
+    best_score: i32,
+    best_index: usize,
+    is_cut: bool,
+}
+
+impl SplitPoint {
+    /// 候補手 `index` の探索結果を反映する
+    ///
+    /// `searched_alpha` は、その候補手を探索した時点での alpha 値。
+    fn update(&mut self, index: usize, score: i32, searched_alpha: i32, beta: i32) {
+        if self.is_cut {
+            return;
+        }
+        if score >= beta {
+            self.is_cut = true;
+            self.best_score = score;
+            self.best_index = index;
+        } else if score > self.alpha
+            || (score == self.alpha && score > searched_alpha && index < self.best_index)
+        {
+            // 同じ評価値の場合は、逐次探索と同じ手を選ぶように、先に並んでいる候補手を優先する
+            self.alpha = score;
+            self.best_score = score;
+            self.best_index = index;
+        } else if score > self.best_score {
+            // 全ての手が alpha 値以下の場合に、評価値の上限として正しい値を返す
+            self.best_score = score;
+        }
+    }
+}
+
+/// 探索の途中経過
+///
+/// aspiration search の各探索 (反復深化の各深さ、および再探索) が終わるごとに、
+/// `Solver::set_observer` で登録した関数に渡される。
+pub struct SearchProgress {
+    pub solver_type: SolverType,
+    /// 探索した深さ (完全読みの場合は空きマス数)
+    pub depth: i32,
+    pub selectivity_percent: i32,
+    /// 探索窓 [alpha, beta]
+    pub alpha: i32,
+    pub beta: i32,
+    pub score: i32,
+    /// `score` が探索窓の外の場合は、評価値の上限または下限
+    pub bound: ScoreBound,
+    pub searched_nodes: u64,
+    pub elapsed: Duration,
+    pub best_move: u64,
+}
+
+type SearchObserver = Box<dyn FnMut(&SearchProgress) + Send>;
 
 pub struct Solver {
     pub search: SearchEngine,
+    helpers: Vec<SearchEngine>,
     candidate_boards: VecDeque<PutBoard>,
     pub print_log: String,
+    observer: Option<SearchObserver>,
+    start_time: Option<Instant>,
 }
 
 impl Solver {
-    pub fn new(evaluator: Evaluator) -> Self {
+    /// 評価関数には `Evaluator` の他、`Evaluate` を実装した任意の型を使える。
+    /// `Arc` に包んで渡すと、読み込んだ評価関数を複数の `Solver` で共有できる。
+    pub fn new(evaluator: impl IntoEvaluator) -> Self {
+        Self::with_search_engine(SearchEngine::new(evaluator))
+    }
+
+    /// 置換表のメモリ使用量 (MB) を指定して生成する
+    ///
+    /// 置換表のエントリ数は 2 のべき乗に切り下げられる。
+    /// 実際のメモリ使用量は `search.t_table.memory_size()` で取得できる。
+    pub fn with_t_table_size_mb(evaluator: impl IntoEvaluator, size_mb: usize) -> Self {
+        Self::with_search_engine(SearchEngine::with_t_table_size_mb(evaluator, size_mb))
+    }
+
+    fn with_search_engine(search: SearchEngine) -> Self {
         Self {
-            search: SearchEngine::new(evaluator),
+            search,
+            helpers: Vec::new(),
             candidate_boards: VecDeque::new(),
             print_log: String::new(),
+            observer: None,
+            start_time: None,
+        }
+    }
+
+    /// 探索の途中経過を受け取る関数を登録する
+    ///
+    /// 経過時間の計測に `Instant::now()` を使用するため、wasm では登録しないこと。
+    pub fn set_observer<F>(&mut self, observer: F)
+    where
+        F: FnMut(&SearchProgress) + Send + 'static,
+    {
+        self.observer = Some(Box::new(observer));
+    }
+
+    pub fn clear_observer(&mut self) {
+        self.observer = None;
+    }
+
+    fn report_progress(&mut self, alpha: i32, beta: i32, score: i32, solver_type: &SolverType) {
+        let Some(observer) = self.observer.as_mut() else {
+            return;
+        };
+        let selectivity_lv = solver_type.selectivity_lv();
+        let bound = if score <= alpha && alpha > -SCORE_MAX {
+            ScoreBound::Upper
+        } else if score >= beta && beta < SCORE_MAX {
+            ScoreBound::Lower
+        } else {
+            ScoreBound::Exact
+        };
+        let progress = SearchProgress {
+            solver_type: *solver_type,
+            depth: solver_type.depth(&self.search.origin_board),
+            selectivity_percent: SELECTIVITY[selectivity_lv as usize].percent,
+            alpha,
+            beta,
+            score,
+            bound,
+            searched_nodes: self.search.status.eval_search_node_count
+                + self.search.status.perfect_search_node_count,
+            elapsed: self.start_time.map(|t| t.elapsed()).unwrap_or_default(),
+            best_move: position_num_to_bit(self.candidate_boards.front().unwrap().put_place as i32).unwrap(),
+        };
+        observer(&progress);
+    }
+
+    /// 探索に使用するスレッド数を設定する (1 以上)
+    ///
+    /// 2 以上を指定すると、ルートと最善応手列の候補の上のノードの子局面を複数スレッドで探索する。
+    /// 置換表は全スレッドで共有される。
+    pub fn set_n_threads(&mut self, n_threads: usize) {
+        let n_helpers = n_threads.max(1) - 1;
+        self.helpers.truncate(n_helpers);
+        while self.helpers.len() < n_helpers {
+            let helper = self.search.new_helper();
+            self.helpers.push(helper);
         }
     }
 
+    pub fn n_threads(&self) -> usize {
+        self.helpers.len() + 1
+    }
+
+    /// MPC のパラメータ表を差し替える (補助エンジンも含む)
+    ///
+    /// 探索に使えない表 (`MpcTable::validate`) の場合は、差し替えずにエラーを返す。
+    /// 置換表に残っている探索結果は古いパラメータによるものなので、探索を始める前に設定すること。
+    pub fn set_mpc_table(&mut self, mpc_table: Arc<MpcTable>) -> Result<(), &'static str> {
+        mpc_table.validate()?;
+        for helper in self.helpers.iter_mut() {
+            helper.mpc_table = Arc::clone(&mpc_table);
+        }
+        self.search.mpc_table = mpc_table;
+        Ok(())
+    }
+
+    /// 置換表をファイルに保存する
+    pub fn save_t_table(&self, path: &str) -> std::io::Result<()> {
+        self.search.t_table.write_file(path, self.search.eval_func.as_ref(), &self.search.mpc_table)
+    }
+
+    /// `save_t_table` で保存した置換表を読み込む
+    ///
+    /// 評価関数 (バージョンと重み) や MPC の表 (`set_mpc_table`) が保存時と異なる場合は、エラーを返す。
+    /// 戻り値は読み込んだエントリ数。
+    pub fn load_t_table(&self, path: &str) -> std::io::Result<usize> {
+        self.search.t_table.read_file(path, self.search.eval_func.as_ref(), &self.search.mpc_table)
+    }
+
     fn aspiration_search(
         &mut self,
         init_width: i32,
         predict_score: i32,
         solver: SolverType,
     ) -> i32 {
+        if let SolverType::Wld(_) = solver {
+            // 勝敗のみを求めるので、0 の周りのヌルウィンドウで探索する
+            let score = self.search_root(-1, 1, &solver).signum();
+            if !self.search.is_aborted() {
+                self.report_progress(-1, 1, score, &solver);
+            }
+            return score;
+        }
+
         let mut left_width = init_width;
         let mut right_width = init_width;
         let mut predict_score = predict_score;
@@ -146,6 +573,10 @@ impl Solver {
 
             // println!("{}: i = {}, [{}, {}]", solver.description(self.search.selectivity_lv),n , alpha, beta);
             predict_score = self.search_root(alpha, beta, &solver);            
+            if self.search.is_aborted() {
+                break;
+            }
+            self.report_progress(alpha, beta, predict_score, &solver);
 
             if (predict_score <= -SCORE_MAX && alpha <= -SCORE_MAX)
                 || (predict_score >= SCORE_MAX && beta >= SCORE_MAX)
@@ -309,11 +740,74 @@ impl Solver {
     }
 
     pub fn solve(&mut self, board: &Board, lv: i32) -> SolverResult {
+        self.solve_with_limits(board, lv, SearchLimits::default())
+    }
+
+    /// 打ち切り条件付きで探索する
+    ///
+    /// 条件を満たした場合は探索を打ち切り、最後に完了した反復の最善手と評価値を返す。
+    /// (`SolverResult::is_aborted` が `true` になる)
+    pub fn solve_with_limits(&mut self, board: &Board, lv: i32, limits: SearchLimits) -> SolverResult {
+        self.solve_board(board, lv, limits, false, None, true)
+    }
+
+    /// 勝敗 (勝ち・引き分け・負け) のみを求める
+    ///
+    /// 終盤の完全読みの代わりに、0 の周りのヌルウィンドウで探索する (`SolverType::Wld`)。
+    /// 正確な石差を求めるよりも速いため、`solve` より 3 マス多い空きマス数から完全読みを行う。
+    /// 完全読みを行った場合、評価値は勝ち +1, 引き分け 0, 負け -1 となる。
+    pub fn solve_wld(&mut self, board: &Board, lv: i32) -> SolverResult {
+        self.solve_board(board, lv, SearchLimits::default(), true, None, true)
+    }
+
+    /// 探索の種類を指定して探索する
+    ///
+    /// 探索レベルと空きマス数から探索の種類を選ばず、常に `solver_type` で探索する。
+    /// 学習データの作成など、決まった深さの探索や完全読みが必要な場合に使う。
+    pub fn solve_with_type(&mut self, board: &Board, solver_type: SolverType) -> SolverResult {
+        self.solve_fixed_type(board, solver_type, true)
+    }
+
+    /// 探索の種類を指定して、評価値のみを求める
+    ///
+    /// `solve_with_type` と同じ探索をするが、最善応手列を復元しない。
+    /// 学習局面の評価値付けなど、多くの局面の評価値だけが必要な場合に使う。
+    pub fn solve_score_with_type(&mut self, board: &Board, solver_type: SolverType) -> i32 {
+        self.solve_fixed_type(board, solver_type, false).eval
+    }
+
+    fn solve_fixed_type(&mut self, board: &Board, solver_type: SolverType, with_pv: bool) -> SolverResult {
+        let (lv, is_wld) = match solver_type {
+            SolverType::Eval(depth, _) => (depth, false),
+            SolverType::Perfect(_) => (AI_LEVEL_MAX as i32, false),
+            SolverType::Wld(_) => (AI_LEVEL_MAX as i32, true),
+        };
+        self.solve_board(board, lv, SearchLimits::default(), is_wld, Some(solver_type), with_pv)
+    }
+
+    /// `fixed_type` が `None` の場合は、探索レベル `lv` から探索の種類を選ぶ (`get_config`)
+    ///
+    /// `with_pv` が `false` の場合は最善応手列を復元せず、`SolverResult::pv` は空になる。
+    fn solve_board(
+        &mut self,
+        board: &Board,
+        lv: i32,
+        limits: SearchLimits,
+        is_wld: bool,
+        fixed_type: Option<SolverType>,
+        with_pv: bool,
+    ) -> SolverResult {
         let lv = lv.clamp(1, 60);
         
         self.search.origin_board = board.clone();
         self.search.status.clear();
-        self.search.t_table.set_old();
+        self.search.t_table.new_search();
+        self.search.limits = limits;
+        self.search.reset_abort();
+        self.start_time = self.observer.as_ref().map(|_| Instant::now());
+        for helper in self.helpers.iter_mut() {
+            helper.limits = self.search.limits.clone();
+        }
 
         let legal_moves = board.moves();
 
@@ -324,25 +818,43 @@ impl Solver {
                 new_board
             };
             if passed_board.moves() == 0 {
+                let score = solve_score(board);
                 return SolverResult {
                     best_move: 0,
-                    eval: solve_score(board),
-                    solver_type: SolverType::Perfect(NO_MPC),
+                    eval: if is_wld { score.signum() } else { score },
+                    solver_type: if is_wld { SolverType::Wld(NO_MPC) } else { SolverType::Perfect(NO_MPC) },
                     searched_nodes: 1,
                     searched_leaf_nodes: 1,
+                    is_aborted: false,
+                    pv: Vec::new(),
+                    is_pv_truncated: false,
                 };
             } else {
-                let mut r = self.solve(&passed_board, lv);
+                let limits = self.search.limits.clone();
+                let mut r = self.solve_board(&passed_board, lv, limits, is_wld, fixed_type, with_pv);
                 r.eval = -r.eval;
+                if with_pv {
+                    r.pv.insert(0, PASS);
+                }
                 return r;
             }
         }
 
         self.candidate_boards = get_put_boards(board, legal_moves).into_iter().collect();
 
-        let mut solver_type = self.get_config(board.empties_count(), lv);
+        let mut solver_type = if let Some(solver_type) = fixed_type {
+            solver_type
+        } else if is_wld {
+            match self.get_config((board.empties_count() - 3).max(0), lv) {
+                SolverType::Perfect(selectivity) => SolverType::Wld(selectivity),
+                solver_type => solver_type,
+            }
+        } else {
+            self.get_config(board.empties_count(), lv)
+        };
 
-        let mut predict_score = self.search.eval_func.clac_features_eval(board);
+        let mut predict_score = self.search.eval_func.evaluate(board, &mut self.search.eval_scratch);
+        let mut last_iteration = None;
 
         // Eval Solver
         self.search.selectivity_lv = if lv > 10 { 1 } else { SELECTIVITY_LV_MAX };
@@ -351,7 +863,7 @@ impl Solver {
             SolverType::Eval(lv,selectivity ) => {
                 
                 // 序盤の評価関数の学習データが良くないので
-                if board.move_count() < 20 && *lv > 14 {
+                if fixed_type.is_none() && board.move_count() < 20 && *lv > 14 {
                     *lv -= 4;
                     if *selectivity != NO_MPC {
                         *selectivity = 3;
@@ -362,12 +874,16 @@ impl Solver {
                 for depth in (start..=*lv).step_by(step as usize) {
                     let init_width: i32 = if depth > 16 { 2 } else { 6 };
                     
-                    predict_score =
-                        self.aspiration_search(init_width, predict_score, SolverType::Eval(depth, *selectivity));
+                    if !self.iterate(init_width, &mut predict_score, SolverType::Eval(depth, *selectivity), &mut last_iteration) {
+                        break;
+                    }
                 }
             },
-            SolverType::Perfect(selectivity) => {        
+            SolverType::Perfect(selectivity) | SolverType::Wld(selectivity) => 'perfect: {        
                 let selectivity = *selectivity;
+                let endgame_solver_type = |selectivity| {
+                    if is_wld { SolverType::Wld(selectivity) } else { SolverType::Perfect(selectivity) }
+                };
                 let eval_solver_lv = std::cmp::min(// perfect solver を使用する際は、反復深化でのEvalSolverレベルを制限
                     (board.empties_count() - 7 - (2 - selectivity/2 )).clamp(2, 24),
                     lv,
@@ -375,54 +891,278 @@ impl Solver {
                 let step = 4; let start = eval_solver_lv.rem_euclid(step);
                 for depth in (start..=eval_solver_lv).step_by(step as usize) {
                     let init_width: i32 = if depth > 16 { 2 } else { 6 };
-                    predict_score =
-                        self.aspiration_search(init_width, predict_score, SolverType::Eval(depth, EVAL_SOLVER_SELECTIVITY));
+                    if !self.iterate(init_width, &mut predict_score, SolverType::Eval(depth, EVAL_SOLVER_SELECTIVITY), &mut last_iteration) {
+                        break 'perfect;
+                    }
                 }
 
                 if eval_solver_lv >= 18 && selectivity > 5 {
                     let init_width = cmp::max(10 - board.empties_count(), 2 + predict_score.rem_euclid(2));
-                    predict_score = self.aspiration_search(init_width, predict_score, SolverType::Perfect(selectivity - 4));
+                    if !self.iterate(init_width, &mut predict_score, endgame_solver_type(selectivity - 4), &mut last_iteration) {
+                        break 'perfect;
+                    }
                 }
 
                 let init_width = cmp::max(10 - board.empties_count(), 2 + predict_score.rem_euclid(2));
-                predict_score = self.aspiration_search(init_width, predict_score, SolverType::Perfect(selectivity));
+                self.iterate(init_width, &mut predict_score, endgame_solver_type(selectivity), &mut last_iteration);
             }
         }
 
         // Perfect solver
 
-        let best_cand = self.candidate_boards.front().unwrap();
+        let is_aborted = self.search.is_aborted();
+        let (best_move, eval, solver_type, pv_depth) = match last_iteration {
+            Some(r) if is_aborted => (r.best_move, r.eval, r.solver_type, r.solver_type.depth(board)),
+            Some(r) => (r.best_move, r.eval, solver_type, solver_type.depth(board)),
+            // 1回も反復が完了しなかった場合は、評価関数の値を返す
+            None => (self.candidate_boards.front().unwrap().put_place, predict_score, solver_type, 1),
+        };
+        let (pv, is_pv_truncated) = if with_pv {
+            self.get_pv(best_move, eval, pv_depth, &solver_type)
+        } else {
+            (Vec::new(), false)
+        };
+        let searched_nodes = self.search.status.eval_search_node_count
+            + self.search.status.perfect_search_node_count;
+        let searched_leaf_nodes = self.search.status.eval_search_leaf_node_count
+            + self.search.status.perfect_search_leaf_node_count;
         SolverResult {
-            best_move: position_num_to_bit(best_cand.put_place as i32).unwrap(),
-            eval: predict_score,
+            best_move: position_num_to_bit(best_move as i32).unwrap(),
+            eval,
             solver_type,
-            searched_nodes: self.search.status.eval_search_node_count
-                + self.search.status.perfect_search_node_count,
-            searched_leaf_nodes: self.search.status.eval_search_leaf_node_count
-                + self.search.status.perfect_search_leaf_node_count,
+            searched_nodes,
+            searched_leaf_nodes,
+            is_aborted,
+            pv,
+            is_pv_truncated,
         }
     }
-    
-    fn search_root(&mut self, alpha: i32, beta: i32, solver_type: &SolverType) -> i32 {
-        match *solver_type {
-            SolverType::Eval(_, selectivity) => self.search.selectivity_lv = selectivity,
-            SolverType::Perfect(selectivity) => self.search.selectivity_lv = selectivity
+
+    /// 置換表に記録された最善手をたどって、最善応手列を復元する
+    ///
+    /// 置換表の最善手は、ヌルウィンドウ探索で上書きされている場合があるため、
+    /// 各局面で評価値 `score` を実現する手であることを確かめてからたどる。
+    /// 確かめる探索のノード数は `self.search.status` に加算される。
+    /// 確かめる探索も打ち切り条件に従い、探索が打ち切られた後は手をたどらない。
+    ///
+    /// 終局または深さ `depth` に達する前に手が見つからなくなった場合や、
+    /// 探索が打ち切られた場合は、2番目の値が `true` になる。
+    fn get_pv(&mut self, best_move: u8, score: i32, depth: i32, solver_type: &SolverType) -> (Vec<u8>, bool) {
+        let mut board = self.search.origin_board.clone();
+        let mut pv = Vec::new();
+        let mut next_move = Some(best_move);
+        let mut score = score;
+        let mut depth = depth;
+        while let Some(m) = next_move {
+            if depth <= 0 {
+                break;
+            }
+            board.put_piece_fast(1 << m);
+            pv.push(m);
+            score = -score;
+            depth -= 1;
+            if board.moves() == 0 {
+                board.swap();
+                if board.moves() == 0 {
+                    break;
+                }
+                pv.push(PASS);
+                score = -score;
+            }
+            if self.search.is_aborted() {
+                return (pv, depth > 0);
+            }
+            next_move = self.find_pv_move(&board, score, depth, solver_type);
+            // 打ち切られた探索の結果では、評価値を実現する手か確かめられない
+            if next_move.is_none() || self.search.is_aborted() {
+                return (pv, depth > 0);
+            }
         }
-        
-        fn pvs_search(board: &Board, alpha: i32, beta: i32, search: &mut SearchEngine, solver_type: &SolverType) -> i32{
-            match solver_type {
-                SolverType::Eval(lv, _) => pvs_eval(board, alpha, beta, *lv - 1, search),
-                SolverType::Perfect(_) => pvs_perfect(board, alpha, beta, search)
+        (pv, false)
+    }
+
+    /// 評価値 `score` を実現する手を探す
+    ///
+    /// 置換表に記録された手から順に、ヌルウィンドウ探索で確かめる。
+    fn find_pv_move(&mut self, board: &Board, score: i32, depth: i32, solver_type: &SolverType) -> Option<u8> {
+        if depth <= 0 {
+            return None;
+        }
+        let solver_type = match *solver_type {
+            SolverType::Eval(_, selectivity) => SolverType::Eval(depth, selectivity),
+            perfect => perfect,
+        };
+
+        let mut candidates = get_put_boards(board, board.moves());
+        if let Some(t) = self.search.t_table.get(board) {
+            for tt_move in t.moves.iter().rev() {
+                if let Some(i) = candidates.iter().position(|c| c.put_place == *tt_move) {
+                    let c = candidates.remove(i);
+                    candidates.insert(0, c);
+                }
+            }
+        }
+
+        // 勝敗のみを求めた場合、負けの局面ではどの手も評価値 (-1) を実現する
+        if matches!(solver_type, SolverType::Wld(_)) && score < 0 {
+            return candidates.first().map(|c| c.put_place);
+        }
+
+        candidates
+            .iter()
+            .find(|c| nws_search(&c.board, -score, &mut self.search, &solver_type) <= -score)
+            .map(|c| c.put_place)
+    }
+
+    /// 手 `put_mask` の評価値が `threshold` 以上かどうかを確かめる
+    ///
+    /// 正確な評価値は求めず、着手後の局面を `threshold` の周りのヌルウィンドウで `solver_type` の探索をして、
+    /// 評価値の下限 (閾値以上の場合) または上限 (閾値未満の場合) を返す。
+    /// 「評価値が X 以下か」は、`threshold` に X + 1 を指定して `is_at_least` が `false` かどうかで確かめられる。
+    ///
+    /// 結果が証明になるのは、MPC を使わない完全読み (`Perfect(NO_MPC)`, `Wld(NO_MPC)`) の場合のみで、
+    /// それ以外の探索の結果は `is_proven` が `false` になる。
+    pub fn check_move(
+        &mut self,
+        board: &Board,
+        put_mask: u64,
+        threshold: i32,
+        solver_type: SolverType,
+    ) -> Result<MoveCheckResult, PutPieceErr> {
+        let mut child = board.clone();
+        child.put(put_mask)?;
+
+        self.search.origin_board = board.clone();
+        self.search.status.clear();
+        self.search.t_table.new_search();
+        self.search.limits = SearchLimits::default();
+        self.search.reset_abort();
+        self.search.selectivity_lv = solver_type.selectivity_lv();
+
+        let mut is_proven = matches!(solver_type, SolverType::Perfect(NO_MPC) | SolverType::Wld(NO_MPC));
+        let score = if child.moves() == 0 {
+            if child.opponent_moves() == 0 {
+                // 着手で終局した場合、評価値は最終石差
+                is_proven = true;
+                -solve_score(&child)
+            } else {
+                // 相手はパスし、再び手番側が打つ
+                let child = child.swapped_board();
+                nws_search(&child, threshold - 1, &mut self.search, &solver_type)
             }
+        } else {
+            -nws_search(&child, -threshold, &mut self.search, &solver_type)
+        };
+        let is_at_least = score >= threshold;
+
+        Ok(MoveCheckResult {
+            is_at_least,
+            score,
+            bound: if is_at_least { ScoreBound::Lower } else { ScoreBound::Upper },
+            is_proven,
+            solver_type,
+            searched_nodes: self.search.status.eval_search_node_count
+                + self.search.status.perfect_search_node_count,
+        })
+    }
+
+    /// ルートの全ての合法手の評価値を求める
+    ///
+    /// 評価値の高い上位 `n_pv` 手は正確な評価値 (`ScoreBound::Exact`) を、
+    /// それ以外の手は評価値の上限 (`ScoreBound::Upper`) を返す。
+    /// 探索レベルと選択性は `solve` と同じで、`solve` の探索で得た置換表を再利用する。
+    /// 結果は評価値の高い順に並ぶ。
+    pub fn solve_multi_pv(&mut self, board: &Board, lv: i32, n_pv: usize) -> Vec<MoveScore> {
+        let legal_moves = board.moves();
+        if legal_moves == 0 {
+            return Vec::new();
         }
+        let n_pv = n_pv.max(1);
 
-        fn nws_search(board: &Board, alpha: i32, search: &mut SearchEngine, solver_type: &SolverType) -> i32{
-            match solver_type {
-                SolverType::Eval(lv, _) => nws_eval(board, alpha, *lv - 1, search),
-                SolverType::Perfect(_) => nws_perfect(board, alpha, search)
+        let result = self.solve(board, lv);
+        let solver_type = result.solver_type;
+        let init_width = match solver_type {
+            SolverType::Eval(depth, _) => if depth > 16 { 2 } else { 6 },
+            SolverType::Perfect(_) | SolverType::Wld(_) => 2,
+        };
+
+        // 各手の評価値を求める探索は、途中経過として通知しない
+        let observer = self.observer.take();
+        let candidates = std::mem::take(&mut self.candidate_boards);
+        let mut move_scores: Vec<MoveScore> = Vec::with_capacity(candidates.len());
+        let mut exact_scores: Vec<i32> = Vec::with_capacity(candidates.len());
+        for (i, candidate) in candidates.iter().enumerate() {
+            // 上位 n_pv 手に入らないことが分かれば、上限のみ求める
+            if exact_scores.len() >= n_pv {
+                let threshold = exact_scores[n_pv - 1];
+                self.search.selectivity_lv = solver_type.selectivity_lv();
+                let score = -nws_search(&candidate.board, -threshold - 1, &mut self.search, &solver_type);
+                if score <= threshold {
+                    move_scores.push(MoveScore { put_place: candidate.put_place, score, bound: ScoreBound::Upper });
+                    continue;
+                }
             }
+
+            let score = if i == 0 {
+                result.eval
+            } else {
+                let predict_score = exact_scores.last().copied().unwrap_or(result.eval);
+                self.candidate_boards = VecDeque::from([PutBoard {
+                    board: candidate.board.clone(),
+                    put_place: candidate.put_place,
+                }]);
+                self.aspiration_search(init_width, predict_score, solver_type)
+            };
+            move_scores.push(MoveScore { put_place: candidate.put_place, score, bound: ScoreBound::Exact });
+            let index = exact_scores.partition_point(|&s| s >= score);
+            exact_scores.insert(index, score);
+        }
+        self.candidate_boards = candidates;
+        self.observer = observer;
+
+        move_scores.sort_by_key(|m| (cmp::Reverse(m.score), m.bound != ScoreBound::Exact));
+        move_scores
+    }
+
+    /// 反復深化の1回分の探索を行う
+    ///
+    /// 探索が打ち切られた場合は `false` を返し、`predict_score`, `last_iteration` は更新しない。
+    fn iterate(
+        &mut self,
+        init_width: i32,
+        predict_score: &mut i32,
+        solver_type: SolverType,
+        last_iteration: &mut Option<IterationResult>,
+    ) -> bool {
+        if self.search.check_limits_now() {
+            return false;
+        }
+        let score = self.aspiration_search(init_width, *predict_score, solver_type);
+        if self.search.is_aborted() {
+            return false;
+        }
+        *predict_score = score;
+        *last_iteration = Some(IterationResult {
+            best_move: self.candidate_boards.front().unwrap().put_place,
+            eval: score,
+            solver_type,
+        });
+        true
+    }
+    
+    fn search_root(&mut self, alpha: i32, beta: i32, solver_type: &SolverType) -> i32 {
+        self.search.selectivity_lv = solver_type.selectivity_lv();
+
+        let is_parallel = !self.helpers.is_empty() && is_split_node(&self.search.origin_board, solver_type);
+
+        if is_parallel {
+            self.search_root_parallel(alpha, beta, solver_type)
+        } else {
+            self.search_root_serial(alpha, beta, solver_type)
         }
+    }
 
+    fn search_root_serial(&mut self, alpha: i32, beta: i32, solver_type: &SolverType) -> i32 {
         let mut alpha = alpha;
         let mut best_cand_index = 0;
         let mut candidate_iter = self.candidate_boards.iter();
@@ -467,4 +1207,723 @@ impl Solver {
 
         best_score
     }
+
+    /// ルートでの並列探索 (Young Brothers Wait Concept)
+    ///
+    /// ルートの候補手を `search_split` で探索し、最善手を `candidate_boards` の先頭に移す。
+    fn search_root_parallel(&mut self, alpha: i32, beta: i32, solver_type: &SolverType) -> i32 {
+        let candidates = std::mem::take(&mut self.candidate_boards);
+        let split = self.search_split(&candidates, alpha, beta, solver_type);
+        self.candidate_boards = candidates;
+
+        if split.is_cut {
+            self.candidate_boards.swap(0, split.best_index);
+        } else if split.best_index > 0 {
+            let best_cand = self.candidate_boards.remove(split.best_index).unwrap();
+            self.candidate_boards.push_front(best_cand);
+        }
+
+        split.best_score
+    }
+
+    /// 子局面 `candidates` を並列に探索する (Young Brothers Wait Concept)
+    ///
+    /// 最初の子局面 (長男) を `search_eldest` で探索して alpha 値を確定させた後、
+    /// 残りの子局面を各スレッドで取り合いながら探索する。
+    /// 各スレッドは、探索開始時点で最も良い alpha 値を用いてヌルウィンドウ探索を行い、
+    /// alpha 値を上回った場合のみ再探索する。
+    ///
+    /// `solver_type` は親局面の探索の種類で、評価関数による探索の場合は親局面からの深さを表す。
+    fn search_split(&mut self, candidates: &VecDeque<PutBoard>, alpha: i32, beta: i32, solver_type: &SolverType) -> SplitPoint {
+        // first move
+        let first_score = -self.search_eldest(&candidates[0].board, -beta, -alpha, solver_type);
+        if first_score >= beta || self.search.is_aborted() {
+            return SplitPoint {
+                alpha,
+                best_score: first_score,
+                best_index: 0,
+                is_cut: first_score >= beta,
+            };
+        }
+
+        let split = Mutex::new(SplitPoint {
+            alpha: cmp::max(alpha, first_score),
+            best_score: first_score,
+            best_index: 0,
+            is_cut: false,
+        });
+        let next_index = AtomicUsize::new(1);
+
+        for helper in self.helpers.iter_mut() {
+            helper.selectivity_lv = self.search.selectivity_lv;
+            helper.origin_board = self.search.origin_board.clone();
+            helper.is_aborted = false;
+        }
+
+        // other move
+        std::thread::scope(|scope| {
+            for helper in self.helpers.iter_mut() {
+                let (split, next_index) = (&split, &next_index);
+                scope.spawn(move || {
+                    split_worker(helper, candidates, next_index, split, beta, solver_type)
+                });
+            }
+            split_worker(&mut self.search, candidates, &next_index, &split, beta, solver_type);
+        });
+
+        for helper in self.helpers.iter_mut() {
+            self.search.status.merge(&helper.status);
+            helper.status.clear();
+        }
+        // 補助エンジンで打ち切られた場合
+        self.search.is_aborted = self.search.abort.load(Ordering::Relaxed);
+
+        split.into_inner().unwrap()
+    }
+
+    /// 長男の局面 `board` を探索する
+    ///
+    /// 残りの深さ (空きマス数) が十分ある場合は、`board` の子局面も `search_split` で並列に探索する。
+    /// これにより、ルートから最善応手列の候補をたどったノードで、順に探索を分割する。
+    fn search_eldest(&mut self, board: &Board, alpha: i32, beta: i32, solver_type: &SolverType) -> i32 {
+        let child_type = match *solver_type {
+            SolverType::Eval(lv, selectivity) => SolverType::Eval(lv - 1, selectivity),
+            other => other,
+        };
+        if !is_split_node(board, &child_type) {
+            return pvs_search(board, alpha, beta, &mut self.search, solver_type);
+        }
+
+        let candidates = get_ordered_put_boards(board, &self.search.t_table);
+        let split = self.search_split(&candidates, alpha, beta, &child_type);
+
+        // 逐次探索 (pvs_perfect, pvs_eval) と同じように、探索結果と最善手を置換表に登録する
+        let lv = match child_type {
+            SolverType::Eval(lv, _) => lv,
+            SolverType::Perfect(_) | SolverType::Wld(_) => 60,
+        };
+        let (min, max) = if split.is_cut {
+            (split.best_score, SCORE_INF)
+        } else if split.best_score > alpha {
+            (split.best_score, split.best_score)
+        } else {
+            (-SCORE_INF, split.best_score)
+        };
+        let best_move = candidates[split.best_index].put_place;
+        self.search.t_table_add(board, min, max, lv, self.search.selectivity_lv, best_move);
+
+        split.best_score
+    }
+}
+
+/// 局面 `board` の子局面を並列に探索するかどうか
+///
+/// `solver_type` は `board` の探索の種類で、評価関数による探索の場合は `board` からの深さを表す。
+/// 浅い探索では、スレッドの起動コストの方が大きくなるため逐次探索を行う。
+fn is_split_node(board: &Board, solver_type: &SolverType) -> bool {
+    board.moves().count_ones() >= 2
+        && match *solver_type {
+            SolverType::Eval(lv, _) => lv >= PARALLEL_SEARCH_MIN_EVAL_LV,
+            SolverType::Perfect(_) | SolverType::Wld(_) => board.empties_count() >= PARALLEL_SEARCH_MIN_EMPTIES,
+        }
+}
+
+/// 着手後の局面を、置換表の最善手、相手の着手可能数の少ない手の順に並べる
+fn get_ordered_put_boards(board: &Board, t_table: &TranspositionTable) -> VecDeque<PutBoard> {
+    let mut put_boards = get_put_boards(board, board.moves());
+    put_boards.sort_by_key(|p| p.board.moves().count_ones());
+    if let Some(t) = t_table.get(board) {
+        for tt_move in t.moves.iter().rev() {
+            if let Some(i) = put_boards.iter().position(|p| p.put_place == *tt_move) {
+                let p = put_boards.remove(i);
+                put_boards.insert(0, p);
+            }
+        }
+    }
+    put_boards.into_iter().collect()
+}
+
+fn pvs_search(board: &Board, alpha: i32, beta: i32, search: &mut SearchEngine, solver_type: &SolverType) -> i32{
+    match solver_type {
+        SolverType::Eval(lv, _) => pvs_eval(board, alpha, beta, *lv - 1, search),
+        SolverType::Perfect(_) | SolverType::Wld(_) => pvs_perfect(board, alpha, beta, search)
+    }
+}
+
+fn nws_search(board: &Board, alpha: i32, search: &mut SearchEngine, solver_type: &SolverType) -> i32{
+    match solver_type {
+        SolverType::Eval(lv, _) => nws_eval(board, alpha, *lv - 1, search),
+        SolverType::Perfect(_) | SolverType::Wld(_) => nws_perfect(board, alpha, search)
+    }
+}
+
+/// `search_split` の各スレッドで実行される探索
+fn split_worker(
+    search: &mut SearchEngine,
+    candidates: &VecDeque<PutBoard>,
+    next_index: &AtomicUsize,
+    split: &Mutex<SplitPoint>,
+    beta: i32,
+    solver_type: &SolverType,
+) {
+    loop {
+        let index = next_index.fetch_add(1, Ordering::Relaxed);
+        let Some(candidate) = candidates.get(index) else {
+            break;
+        };
+        let alpha = {
+            let split = split.lock().unwrap();
+            if split.is_cut {
+                break;
+            }
+            split.alpha
+        };
+
+        let mut score = -nws_search(&candidate.board, -alpha - 1, search, solver_type);
+        if score > alpha && score < beta {
+            // 再探索
+            score = -pvs_search(&candidate.board, -beta, -alpha, search, solver_type);
+        }
+
+        split.lock().unwrap().update(index, score, alpha, beta);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::Evaluator;
+
+    fn board_from_obf(s: &str) -> Board {
+        let mut board = Board { player: 0, opponent: 0 };
+        for (i, c) in s.chars().enumerate() {
+            match c {
+                'X' => board.player |= 1 << i,
+                'O' => board.opponent |= 1 << i,
+                _ => (),
+            }
+        }
+        board
+    }
+
+    /// fforum-1-19.obf の #1 から #5
+    const FFORUM: [&str; 5] = [
+        "--XXXXX--OOOXX-O-OOOXXOX-OXOXOXXOXXXOXXX--XOXOXX-XXXOOO--OOOOO--",
+        "-XXXXXX---XOOOO--XOXXOOX-OOOOOOOOOOOXXOOOOOXXOOX--XXOO----XXXXX-",
+        "----OX----OOXX---OOOXX-XOOXXOOOOOXXOXXOOOXXXOOOOOXXXXOXO--OOOOOX",
+        "-XXXXXX-X-XXXOO-XOXXXOOXXXOXOOOX-OXOOXXX--OOOXXX--OOXX----XOXXO-",
+        "-OOOOO----OXXO-XXXOXOXX-XXOXOXXOXXOOXOOOXXXXOO-OX-XOOO---XXXXX--",
+    ];
+
+    /// fforum-1-19.obf #1 (空きマス 14, G8: +18)
+    fn fforum_1() -> Board {
+        board_from_obf(FFORUM[0])
+    }
+
+    /// fforum の各問題を、空きマスが `n_empties` 個になるまで最も番号の小さいマスに打ち進めた局面
+    ///
+    /// 完全読みの結果を、小さい局面で子局面の探索と比べるために使う。
+    fn small_positions(n_empties: i32) -> Vec<Board> {
+        FFORUM
+            .iter()
+            .filter_map(|obf| {
+                let mut board = board_from_obf(obf);
+                while board.empties_count() > n_empties {
+                    if board.moves() == 0 {
+                        board.swap();
+                        if board.moves() == 0 {
+                            return None;
+                        }
+                    }
+                    board.put_piece_fast(1 << board.moves().trailing_zeros());
+                }
+                Some(board)
+            })
+            .collect()
+    }
+
+    /// 初期局面から、空きマスが `n_empties` 個になるまで最も番号の小さいマスに打ち進めた局面
+    ///
+    /// fforum の問題 (空きマス 14) よりも深い探索で、並列探索の分割を行うために使う。
+    fn opening_line_position(n_empties: i32) -> Board {
+        let mut board = Board::new();
+        while board.empties_count() > n_empties {
+            if board.moves() == 0 {
+                board.swap();
+            }
+            board.put_piece_fast(1 << board.moves().trailing_zeros());
+        }
+        board
+    }
+
+    #[test]
+    fn parallel_search_matches_serial_search() {
+        let mut problems: Vec<Board> = FFORUM.iter().map(|obf| board_from_obf(obf)).collect();
+        problems.push(opening_line_position(17));
+
+        let mut serial = Solver::new(Evaluator::zero());
+        let mut parallel = Solver::new(Evaluator::zero());
+        parallel.set_n_threads(4);
+
+        for board in problems {
+            let serial_result = serial.solve(&board, 60);
+            let parallel_result = parallel.solve(&board, 60);
+            assert_eq!(serial_result.eval, parallel_result.eval);
+
+            // 同じ評価値の手が複数ある場合、どの手を選ぶかはスレッドの探索順による
+            let mut child = board.clone();
+            child.put_piece_fast(parallel_result.best_move);
+            assert_eq!(-serial.solve(&child, 60).eval, parallel_result.eval);
+        }
+    }
+
+    #[test]
+    fn solvers_share_one_evaluator() {
+        let evaluator = Arc::new(Evaluator::zero());
+        let mut solver = Solver::new(Arc::clone(&evaluator));
+        solver.set_n_threads(3);
+        let mut other = Solver::new(Arc::clone(&evaluator));
+
+        // 補助エンジンを含め、重みは複製されない
+        assert_eq!(Arc::strong_count(&evaluator), 5);
+        assert!(solver.helpers.iter().all(|helper| std::ptr::addr_eq(Arc::as_ptr(&helper.eval_func), Arc::as_ptr(&evaluator))));
+
+        let board = fforum_1();
+        assert_eq!(solver.solve(&board, 10).eval, other.solve(&board, 10).eval);
+    }
+
+    #[test]
+    fn search_works_with_other_evaluators() {
+        let board = fforum_1();
+        let mut pattern = Solver::new(Evaluator::zero());
+        let mut simple = Solver::new(SimpleEvaluator);
+
+        // 完全読みの結果は評価関数によらない
+        assert_eq!(pattern.solve(&board, 60).eval, simple.solve(&board, 60).eval);
+
+        let board = Board::new();
+        let result = simple.solve(&board, 8);
+        assert!(result.best_move & board.moves() != 0);
+    }
+
+    #[test]
+    fn solve_with_type_does_not_depend_on_the_level() {
+        let board = fforum_1();
+        let mut solver = Solver::new(Evaluator::zero());
+
+        let perfect = solver.solve_with_type(&board, SolverType::Perfect(NO_MPC));
+        assert!(matches!(perfect.solver_type, SolverType::Perfect(NO_MPC)));
+        assert_eq!(perfect.eval, solver.solve(&board, 60).eval);
+
+        let eval = solver.solve_with_type(&board, SolverType::Eval(4, NO_MPC));
+        assert!(matches!(eval.solver_type, SolverType::Eval(4, NO_MPC)));
+        assert!(eval.best_move & board.moves() != 0);
+    }
+
+    #[test]
+    fn score_only_solve_skips_the_pv() {
+        let board = fforum_1();
+        let mut solver = Solver::new(Evaluator::zero());
+        for solver_type in [SolverType::Perfect(NO_MPC), SolverType::Eval(6, NO_MPC)] {
+            let with_pv = solver.solve_with_type(&board, solver_type);
+            let pv_nodes = with_pv.searched_nodes;
+            let score = solver.solve_score_with_type(&board, solver_type);
+            assert_eq!(score, with_pv.eval);
+            // 最善応手列を確かめる探索をしない分、ノード数が少ない
+            let score_nodes = solver.search.status.eval_search_node_count + solver.search.status.perfect_search_node_count;
+            assert!(score_nodes < pv_nodes);
+        }
+    }
+
+    #[test]
+    fn solve_with_limits_returns_completed_iteration() {
+        let board = fforum_1();
+        let mut solver = Solver::new(Evaluator::zero());
+
+        let limits = SearchLimits {
+            max_nodes: Some(2000),
+            ..Default::default()
+        };
+        let result = solver.solve_with_limits(&board, 60, limits);
+        assert!(result.is_aborted);
+        assert!(result.best_move & board.moves() != 0);
+
+        let limits = SearchLimits {
+            stop: Some(Arc::new(AtomicBool::new(true))),
+            ..Default::default()
+        };
+        let result = solver.solve_with_limits(&board, 60, limits);
+        assert!(result.is_aborted);
+        assert!(result.best_move & board.moves() != 0);
+
+        let result = solver.solve(&board, 60);
+        assert!(!result.is_aborted);
+    }
+
+    #[test]
+    fn pv_does_not_exceed_the_node_limit() {
+        let mut solver = Solver::new(Evaluator::zero());
+        for obf in FFORUM {
+            let board = board_from_obf(obf);
+            for max_nodes in [5_000, 50_000, 500_000] {
+                let limits = SearchLimits {
+                    max_nodes: Some(max_nodes),
+                    ..Default::default()
+                };
+                let result = solver.solve_with_limits(&board, 60, limits);
+                // 打ち切り条件の確認は一定のノード数ごとに行うため、上限をわずかに超える場合がある
+                assert!(result.searched_nodes < max_nodes + 10_000, "{} nodes for {max_nodes}", result.searched_nodes);
+                assert_eq!(result.pv[0], position_bit_to_num(result.best_move).unwrap());
+                if result.is_aborted {
+                    assert!(result.is_pv_truncated);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn observer_reports_each_search_until_the_result() {
+        struct Report {
+            is_perfect: bool,
+            depth: i32,
+            score: i32,
+            bound: ScoreBound,
+            alpha: i32,
+            beta: i32,
+            searched_nodes: u64,
+            best_move: u64,
+        }
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let mut solver = Solver::new(Evaluator::zero());
+        let log = Arc::clone(&reports);
+        solver.set_observer(move |p| {
+            log.lock().unwrap().push(Report {
+                is_perfect: matches!(p.solver_type, SolverType::Perfect(_)),
+                depth: p.depth,
+                score: p.score,
+                bound: p.bound,
+                alpha: p.alpha,
+                beta: p.beta,
+                searched_nodes: p.searched_nodes,
+                best_move: p.best_move,
+            })
+        });
+
+        let board = fforum_1();
+        let result = solver.solve(&board, 60);
+        let n_reports = {
+            let reports = reports.lock().unwrap();
+            // 評価関数による反復深化の後に完全読みをする
+            assert!(reports.len() >= 2);
+            assert!(!reports[0].is_perfect);
+            for r in reports.iter() {
+                assert!(r.best_move & board.moves() != 0);
+                match r.bound {
+                    ScoreBound::Upper => assert!(r.score <= r.alpha),
+                    ScoreBound::Lower => assert!(r.score >= r.beta),
+                    ScoreBound::Exact => (),
+                }
+            }
+            assert!(reports.windows(2).all(|pair| pair[0].searched_nodes <= pair[1].searched_nodes));
+
+            let last = reports.last().unwrap();
+            assert!(last.is_perfect);
+            assert_eq!(last.depth, board.empties_count());
+            assert_eq!((last.score, last.bound, last.best_move), (result.eval, ScoreBound::Exact, result.best_move));
+            reports.len()
+        };
+
+        solver.clear_observer();
+        solver.solve(&board, 60);
+        assert_eq!(reports.lock().unwrap().len(), n_reports);
+    }
+
+    #[test]
+    fn perfect_pv_reaches_the_solved_score() {
+        let problems = &FFORUM[..3];
+
+        let mut solver = Solver::new(Evaluator::zero());
+        for obf in problems {
+            let root = board_from_obf(obf);
+            let result = solver.solve(&root, 60);
+            assert_eq!(result.pv[0], position_bit_to_num(result.best_move).unwrap());
+            assert!(!result.is_pv_truncated);
+            // 最善応手列を確かめる探索のノード数も数える
+            assert_eq!(
+                result.searched_nodes,
+                solver.search.status.eval_search_node_count + solver.search.status.perfect_search_node_count
+            );
+
+            let mut board = root.clone();
+            let mut is_root_player = true;
+            for &m in result.pv.iter() {
+                if m == PASS {
+                    board.swap();
+                } else {
+                    assert!(board.put(1 << m).is_ok());
+                }
+                is_root_player = !is_root_player;
+            }
+            assert_eq!(board.moves() | board.opponent_moves(), 0);
+            let score = solve_score(&board);
+            assert_eq!(if is_root_player { score } else { -score }, result.eval);
+        }
+    }
+
+    #[test]
+    fn pv_starts_with_a_pass_when_the_root_player_passes() {
+        // F8 を打つと、白は打てずにパスする
+        let mut board = board_from_obf("-OOOO-X--OXOOOOOOOXXOOOOOOOOXXOOOOOOOXOOOOOOOXOX---OOOOX--XOO-OX");
+        board.put(position_str_to_bit("F8").unwrap()).unwrap();
+        assert!(board.moves() == 0 && board.opponent_moves() != 0);
+
+        let mut solver = Solver::new(Evaluator::zero());
+        let result = solver.solve(&board, 60);
+        assert_eq!(result.pv[0], PASS);
+        assert_eq!(result.pv[1], position_bit_to_num(result.best_move).unwrap());
+        assert!(result.best_move & board.opponent_moves() != 0);
+        assert_eq!(result.eval, -solver.solve(&board.swapped_board(), 60).eval);
+        assert!(result.pv_string().starts_with("pass "));
+    }
+
+    #[test]
+    fn truncated_pv_is_marked() {
+        let mut result = SolverResult {
+            best_move: position_str_to_bit("G8").unwrap(),
+            eval: 0,
+            solver_type: SolverType::Eval(4, NO_MPC),
+            searched_nodes: 0,
+            searched_leaf_nodes: 0,
+            is_aborted: false,
+            pv: vec![62, PASS, 61],
+            is_pv_truncated: false,
+        };
+        assert_eq!(result.pv_string(), "G8 pass F8");
+        result.is_pv_truncated = true;
+        assert_eq!(result.pv_string(), "G8 pass F8 ...");
+    }
+
+    #[test]
+    fn multi_pv_matches_child_searches() {
+        let problems = [FFORUM[0], FFORUM[2]];
+
+        let mut solver = Solver::new(Evaluator::zero());
+        let mut child_solver = Solver::new(Evaluator::zero());
+        for obf in problems {
+            let board = board_from_obf(obf);
+            let best = solver.solve(&board, 60);
+
+            let all_moves = solver.solve_multi_pv(&board, 60, 64);
+            assert_eq!(all_moves.len() as u32, board.moves().count_ones());
+            assert_eq!(all_moves[0].score, best.eval);
+            for m in all_moves.iter() {
+                assert_eq!(m.bound, ScoreBound::Exact);
+                let mut child = board.clone();
+                child.put_piece_fast(1 << m.put_place);
+                assert_eq!(m.score, -child_solver.solve(&child, 60).eval);
+            }
+
+            let top_move = solver.solve_multi_pv(&board, 60, 1);
+            assert_eq!(top_move[0].score, best.eval);
+            assert_eq!(top_move[0].bound, ScoreBound::Exact);
+            for m in top_move[1..].iter() {
+                let exact = all_moves.iter().find(|e| e.put_place == m.put_place).unwrap();
+                assert!(m.score <= best.eval);
+                assert!(m.bound == ScoreBound::Upper || m.score == exact.score);
+                if m.bound == ScoreBound::Upper {
+                    assert!(exact.score <= m.score);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn multi_pv_orders_moves_and_limits_exact_scores() {
+        let mut solver = Solver::new(Evaluator::zero());
+        let mut child_solver = Solver::new(Evaluator::zero());
+        for board in small_positions(10) {
+            let n_moves = board.moves().count_ones() as usize;
+            let exact_scores: Vec<(u8, i32)> = get_put_boards(&board, board.moves())
+                .iter()
+                .map(|c| (c.put_place, -child_solver.solve(&c.board, 60).eval))
+                .collect();
+            let best = exact_scores.iter().map(|&(_, score)| score).max().unwrap();
+
+            // n_pv が 0 の場合は 1 として扱い、合法手の数より多い場合は全ての手が正確な評価値になる
+            for n_pv in [0, 1, 2, n_moves, n_moves + 5] {
+                let move_scores = solver.solve_multi_pv(&board, 60, n_pv);
+                assert_eq!(move_scores.len(), n_moves);
+                assert_eq!((move_scores[0].score, move_scores[0].bound), (best, ScoreBound::Exact));
+                let n_exact = move_scores.iter().filter(|m| m.bound == ScoreBound::Exact).count();
+                assert!(n_exact >= n_pv.clamp(1, n_moves));
+
+                // 評価値の高い順に並び、同じ評価値では正確な評価値が先に並ぶ
+                for pair in move_scores.windows(2) {
+                    assert!(pair[0].score >= pair[1].score);
+                    if pair[0].score == pair[1].score {
+                        assert!(pair[0].bound == ScoreBound::Exact || pair[1].bound != ScoreBound::Exact);
+                    }
+                }
+                for m in move_scores.iter() {
+                    let &(_, exact) = exact_scores.iter().find(|&&(p, _)| p == m.put_place).unwrap();
+                    match m.bound {
+                        ScoreBound::Exact => assert_eq!(m.score, exact),
+                        ScoreBound::Upper => assert!(exact <= m.score),
+                        ScoreBound::Lower => panic!("multi-PV returned a lower bound"),
+                    }
+                }
+            }
+        }
+
+        // 合法手がない (パスする) 局面では、手を返さない
+        let mut board = board_from_obf("-OOOO-X--OXOOOOOOOXXOOOOOOOOXXOOOOOOOXOOOOOOOXOX---OOOOX--XOO-OX");
+        board.put(position_str_to_bit("F8").unwrap()).unwrap();
+        assert!(board.moves() == 0 && board.opponent_moves() != 0);
+        assert!(solver.solve_multi_pv(&board, 60, 3).is_empty());
+    }
+
+    #[test]
+    fn wld_matches_sign_of_exact_score() {
+        let problems = &FFORUM[..4];
+
+        let mut solver = Solver::new(Evaluator::zero());
+        for obf in problems {
+            let board = board_from_obf(obf);
+            for board in [board.clone(), board.swapped_board()] {
+                let exact = solver.solve(&board, 60);
+                let wld = solver.solve_wld(&board, 60);
+                assert!(matches!(wld.solver_type, SolverType::Wld(_)));
+                assert_eq!(wld.eval, exact.eval.signum());
+            }
+        }
+    }
+
+    #[test]
+    fn wld_best_move_keeps_the_result_on_small_positions() {
+        let mut solver = Solver::new(Evaluator::zero());
+        let mut child_solver = Solver::new(Evaluator::zero());
+        for board in small_positions(10) {
+            for board in [board.clone(), board.swapped_board()] {
+                if board.moves() == 0 {
+                    continue;
+                }
+                let exact = solver.solve(&board, 60).eval;
+                let wld = solver.solve_wld(&board, 60);
+                assert!(matches!(wld.solver_type, SolverType::Wld(NO_MPC)));
+                // 評価値は勝ち (1), 引き分け (0), 負け (-1) のみ
+                assert_eq!(wld.eval, exact.signum());
+
+                // 最善手を打った後の局面の最終石差も、同じ勝敗になる
+                let mut child = board.clone();
+                child.put_piece_fast(wld.best_move);
+                assert_eq!((-child_solver.solve(&child, 60).eval).signum(), wld.eval);
+            }
+        }
+    }
+
+    #[test]
+    fn check_move_bounds_the_exact_score() {
+        let board = fforum_1();
+        let mut solver = Solver::new(Evaluator::zero());
+        let g8 = position_str_to_bit("G8").unwrap();
+        let exact = SolverType::Perfect(NO_MPC);
+
+        let result = solver.check_move(&board, g8, 18, exact).unwrap();
+        assert!(result.is_at_least && result.is_proven);
+        assert_eq!(result.bound, ScoreBound::Lower);
+        assert!(result.score >= 18);
+
+        let result = solver.check_move(&board, g8, 19, exact).unwrap();
+        assert!(!result.is_at_least && result.is_proven);
+        assert_eq!(result.bound, ScoreBound::Upper);
+        assert!(result.score <= 18);
+
+        // MPC を使う探索や評価関数による探索の結果は、証明にならない
+        for solver_type in [SolverType::Perfect(0), SolverType::Eval(6, NO_MPC)] {
+            let result = solver.check_move(&board, g8, 18, solver_type).unwrap();
+            assert!(!result.is_proven);
+        }
+
+        assert!(solver.check_move(&board, position_str_to_bit("A1").unwrap(), 0, exact).is_err());
+    }
+
+    #[test]
+    fn check_move_handles_a_pass_after_the_move() {
+        // F8 を打つと、白は打てずにパスする
+        let board = board_from_obf("-OOOO-X--OXOOOOOOOXXOOOOOOOOXXOOOOOOOXOOOOOOOXOX---OOOOX--XOO-OX");
+        let f8 = position_str_to_bit("F8").unwrap();
+        let mut child = board.clone();
+        child.put(f8).unwrap();
+        assert!(child.moves() == 0 && child.opponent_moves() != 0);
+
+        let mut solver = Solver::new(Evaluator::zero());
+        let expected = solver.solve(&child.swapped_board(), 60).eval;
+        for threshold in expected - 2..=expected + 2 {
+            let result = solver.check_move(&board, f8, threshold, SolverType::Perfect(NO_MPC)).unwrap();
+            assert!(result.is_proven);
+            assert_eq!(result.is_at_least, expected >= threshold);
+        }
+
+        // 着手で終局する場合は、最終石差と比べる
+        let end = board_from_obf("XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXO-");
+        let h8 = position_str_to_bit("H8").unwrap();
+        let result = solver.check_move(&end, h8, 64, SolverType::Eval(4, 0)).unwrap();
+        assert!(result.is_at_least && result.is_proven);
+        assert_eq!(result.score, 64);
+    }
+
+    #[test]
+    fn split_node_result_is_stored_in_the_t_table() {
+        // 子局面の空きマスが 13 個なので、子局面も並列に探索される
+        let board = fforum_1();
+        let exact = SolverType::Perfect(NO_MPC);
+        assert!(is_split_node(&board, &exact));
+        let expected = Solver::new(Evaluator::zero()).solve(&board, 60).eval;
+
+        // 探索窓の内側 (正確な値), 下側 (上限), 上側 (下限)
+        for (alpha, beta) in [(-SCORE_MAX, SCORE_MAX), (expected + 1, expected + 3), (expected - 3, expected - 1)] {
+            let mut solver = Solver::new(Evaluator::zero());
+            solver.set_n_threads(2);
+            solver.search.selectivity_lv = NO_MPC;
+            let score = solver.search_eldest(&board, alpha, beta, &SolverType::Perfect(NO_MPC));
+
+            let entry = solver.search.t_table.get(&board).unwrap();
+            assert_eq!(entry.lv, 60);
+            assert_eq!(entry.selectivity_lv as i32, NO_MPC);
+            assert_ne!(board.moves() & (1 << entry.moves[0]), 0);
+            let (min, max) = (entry.min as i32, entry.max as i32);
+            if score <= alpha {
+                assert!(max == score && expected <= max, "{} {}", score, max);
+            } else if score >= beta {
+                assert!(min == score && expected >= min, "{} {}", score, min);
+            } else {
+                assert_eq!((min, max), (expected, expected));
+            }
+        }
+    }
+
+    #[test]
+    fn set_mpc_table_rejects_invalid_tables() {
+        let mut solver = Solver::new(Evaluator::zero());
+        solver.set_n_threads(2);
+
+        // 空きマス数ごとのパラメータが足りない
+        let mut short = MpcTable::default();
+        short.perfect_search.truncate(60);
+        assert!(solver.set_mpc_table(Arc::new(short)).is_err());
+
+        // e_std が 0 だと、全てのノードで枝刈りしてしまう
+        let mut zero_std = MpcTable::default();
+        zero_std.perfect_search.iter_mut().flatten().next().unwrap().e_std = 0.0;
+        assert!(solver.set_mpc_table(Arc::new(zero_std)).is_err());
+
+        // 差し替えに失敗した場合は、元の表のまま
+        assert_eq!(*solver.search.mpc_table, MpcTable::default());
+        assert_eq!(*solver.helpers[0].mpc_table, MpcTable::default());
+
+        let mut table = MpcTable::default();
+        table.perfect_search[20] = None;
+        solver.set_mpc_table(Arc::new(table.clone())).unwrap();
+        assert_eq!(*solver.helpers[0].mpc_table, table);
+    }
 }
@@ -0,0 +1,227 @@
+// Stable discs
+// Optimization ideas :
+//    edax-reversi (Richard Delorme, Toshihiko Okuhara)
+//    Edax src               : https://github.com/abulmo/edax-reversi
+
+use crate::bit::transpose;
+use std::sync::OnceLock;
+
+/// 辺の確定石のテーブル
+///
+/// index: 自分の石 (8bit) * 256 + 相手の石 (8bit)
+/// value: 自分の石のうち、辺の上で確定している石
+static EDGE_STABLE: OnceLock<Box<[u8]>> = OnceLock::new();
+
+fn edge_stable_table() -> &'static [u8] {
+    EDGE_STABLE.get_or_init(|| {
+        let mut table = vec![0u8; 256 * 256].into_boxed_slice();
+        for p in 0..256u32 {
+            for o in 0..256u32 {
+                if p & o == 0 {
+                    table[(p * 256 + o) as usize] = find_edge_stable(p as u8, o as u8, p as u8);
+                }
+            }
+        }
+        table
+    })
+}
+
+/// 辺の確定石のテーブルを作る
+///
+/// 初めて使うときに作ると探索の時間に含まれてしまうため、探索エンジンの生成時に呼ぶ。
+pub(crate) fn init_edge_stable_table() {
+    edge_stable_table();
+}
+
+/// 辺の上で `p` 側の石を反転させる着手を全て試し、反転されなかった石を返す
+///
+/// 合法手かどうかに関わらず、どちらの手番でも任意の空きマスに打てるものとして調べる。
+fn find_edge_stable(p: u8, o: u8, stable: u8) -> u8 {
+    let stable = stable & p;
+    let empties = !(p | o);
+    if stable == 0 || empties == 0 {
+        return stable;
+    }
+
+    let mut stable = stable;
+    for x in 0..8 {
+        let bit = 1u8 << x;
+        if empties & bit == 0 {
+            continue;
+        }
+        // 自分が打つ
+        let (next_p, next_o) = put_edge(p | bit, o, x);
+        stable = find_edge_stable(next_p, next_o, stable);
+        if stable == 0 {
+            return 0;
+        }
+        // 相手が打つ
+        let (next_o, next_p) = put_edge(o | bit, p, x);
+        stable = find_edge_stable(next_p, next_o, stable);
+        if stable == 0 {
+            return 0;
+        }
+    }
+    stable
+}
+
+/// 辺の上で `x` に打った `p` 側の石で、`o` 側の石を反転させる
+fn put_edge(p: u8, o: u8, x: i32) -> (u8, u8) {
+    let (mut p, mut o) = (p, o);
+    for dir in [-1i32, 1] {
+        let mut flip = 0u8;
+        let mut y = x + dir;
+        while (0..8).contains(&y) && o & (1 << y) != 0 {
+            flip |= 1 << y;
+            y += dir;
+        }
+        if (0..8).contains(&y) && p & (1 << y) != 0 {
+            p |= flip;
+            o &= !flip;
+        }
+    }
+    (p, o)
+}
+
+/// 4辺の確定石
+#[inline(always)]
+fn get_stable_edge(p: u64, o: u64) -> u64 {
+    let table = edge_stable_table();
+    let edge = |p: u64, o: u64| table[((p & 0xFF) * 256 + (o & 0xFF)) as usize] as u64;
+
+    let (tp, to) = (transpose(p), transpose(o));
+    edge(p, o)
+        | edge(p >> 56, o >> 56) << 56
+        | transpose(edge(tp, to))
+        | transpose(edge(tp >> 56, to >> 56) << 56)
+}
+
+/// `gen` を方向 `shift` (左シフト) に盤端まで伸ばす
+#[inline(always)]
+fn fill_left(gen: u64, shift: u32, mask: u64) -> u64 {
+    let mut g = gen;
+    let mut m = mask;
+    g |= m & (g << shift);
+    m &= m << shift;
+    g |= m & (g << (shift * 2));
+    m &= m << (shift * 2);
+    g |= m & (g << (shift * 4));
+    g
+}
+
+/// `gen` を方向 `shift` (右シフト) に盤端まで伸ばす
+#[inline(always)]
+fn fill_right(gen: u64, shift: u32, mask: u64) -> u64 {
+    let mut g = gen;
+    let mut m = mask;
+    g |= m & (g >> shift);
+    m &= m >> shift;
+    g |= m & (g >> (shift * 2));
+    m &= m >> (shift * 2);
+    g |= m & (g >> (shift * 4));
+    g
+}
+
+/// 空きマスを含まないライン上のマス
+///
+/// 戻り値: [横, 縦, 斜め (右上-左下), 斜め (左上-右下)]
+#[inline(always)]
+fn get_full_lines(occupied: u64) -> [u64; 4] {
+    let empties = !occupied;
+    let line_with_empty = |shift: u32, mask_left: u64, mask_right: u64| {
+        fill_left(empties, shift, mask_left) | fill_right(empties, shift, mask_right)
+    };
+    [
+        !line_with_empty(1, 0xfefefefefefefefe, 0x7f7f7f7f7f7f7f7f),
+        !line_with_empty(8, u64::MAX, u64::MAX),
+        !line_with_empty(7, 0x7f7f7f7f7f7f7f7f, 0xfefefefefefefefe),
+        !line_with_empty(9, 0xfefefefefefefefe, 0x7f7f7f7f7f7f7f7f),
+    ]
+}
+
+/// `p` 側の確定石 (の一部) を求める
+///
+/// 辺の確定石、4方向のラインが全て埋まっている石、
+/// および4方向それぞれで隣に確定石があるかラインが埋まっている石を確定石とする。
+/// 全ての確定石を求めるわけではないが、求めた石は必ず確定石である。
+#[inline(always)]
+pub fn get_stable_discs(p: u64, o: u64) -> u64 {
+    const CENTRAL: u64 = 0x007e7e7e7e7e7e00;
+
+    let [full_h, full_v, full_d7, full_d9] = get_full_lines(p | o);
+    let p_central = p & CENTRAL;
+    let mut stable = get_stable_edge(p, o) | (full_h & full_v & full_d7 & full_d9 & p_central);
+    if stable == 0 {
+        return 0;
+    }
+
+    loop {
+        let old_stable = stable;
+        let stable_h = (stable >> 1) | (stable << 1) | full_h;
+        let stable_v = (stable >> 8) | (stable << 8) | full_v;
+        let stable_d7 = (stable >> 7) | (stable << 7) | full_d7;
+        let stable_d9 = (stable >> 9) | (stable << 9) | full_d9;
+        stable |= stable_h & stable_v & stable_d7 & stable_d9 & p_central;
+        if stable == old_stable {
+            return stable;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+    use crate::eval_simple::put_random_piece;
+
+    /// ランダムに終局まで打ち進め、(最初の手番側の石, もう一方の石) を返す
+    fn random_playout(board: &Board) -> (u64, u64) {
+        let mut board = board.clone();
+        let mut is_first_player = true;
+        loop {
+            if put_random_piece(&mut board).is_err() {
+                // パス
+                board.swap();
+                is_first_player = !is_first_player;
+                if board.moves() == 0 {
+                    break;
+                }
+                continue;
+            }
+            is_first_player = !is_first_player;
+        }
+        if is_first_player {
+            (board.player, board.opponent)
+        } else {
+            (board.opponent, board.player)
+        }
+    }
+
+    #[test]
+    fn stable_discs_are_never_flipped() {
+        let mut n_stable = 0;
+        for _ in 0..200 {
+            let mut board = Board::new();
+            while board.moves() != 0 {
+                let (p_stable, o_stable) = (board.stable_discs(), board.opponent_stable_discs());
+                assert_eq!(p_stable & !board.player, 0);
+                assert_eq!(o_stable & !board.opponent, 0);
+                n_stable += (p_stable | o_stable).count_ones();
+                for _ in 0..4 {
+                    let (p, o) = random_playout(&board);
+                    assert_eq!(p_stable & !p, 0);
+                    assert_eq!(o_stable & !o, 0);
+                }
+                put_random_piece(&mut board).unwrap();
+            }
+        }
+        assert!(n_stable > 0);
+    }
+
+    #[test]
+    fn full_board_is_stable() {
+        let p = 0x0123456789abcdef;
+        assert_eq!(get_stable_discs(p, !p), p);
+        assert_eq!(get_stable_discs(!p, p), !p);
+    }
+}
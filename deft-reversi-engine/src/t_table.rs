@@ -1,32 +1,142 @@
 use crate::board::*;
+use crate::evaluate::Evaluate;
+use crate::mpc::MpcTable;
+use bincode::Options;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
 
-const TABLE_SIZE: usize = 1 << 22;
+/// 置換表のデフォルトのエントリ数
+const DEFAULT_TABLE_SIZE: usize = 1 << 22;
+/// 置換表の最小のエントリ数
+const MIN_TABLE_SIZE: usize = 1 << 10;
+/// 置換表の最大のエントリ数 (ハッシュ値が 32bit のため)
+const MAX_TABLE_SIZE: usize = 1 << 31;
+/// 1つのバケットに含まれるエントリ数
+const BUCKET_SIZE: usize = 4;
+
+/// 置換表のスナップショットファイルの識別子
+const SNAPSHOT_MAGIC: [u8; 4] = *b"DRTT";
+/// スナップショットの形式のバージョン (`TableData` やヘッダの形式を変えたら更新する)
+const SNAPSHOT_VERSION: u32 = 3;
+/// スナップショットのヘッダの最大サイズ (バイト)
+///
+/// 壊れたファイルが巨大な文字列や配列の長さを含んでいても、大きなメモリを確保しないように制限する。
+const SNAPSHOT_HEADER_LIMIT: u64 = 1 << 16;
+
+/// 置換表のスナップショットファイルのヘッダ
+///
+/// 評価関数や MPC のパラメータが異なると探索結果も異なるため、評価関数のバージョンと重みのチェックサム、
+/// MPC の表を記録しておき、読み込み時に一致しなければ拒否する。
+#[derive(Serialize, Deserialize)]
+struct SnapshotHeader {
+    magic: [u8; 4],
+    version: u32,
+    eval_version: String,
+    eval_checksum: u32,
+    mpc_table: MpcTable,
+    n_entries: u64,
+}
 pub const N_TT_MOVES: usize = 2;
 
-#[derive(Clone)]
+#[derive(Clone, Copy)]
 pub struct TableData {
-    pub board: Board,
     pub max: i8,
     pub min: i8,
     pub lv: u8,
     pub selectivity_lv: u8,
     pub moves: [u8; N_TT_MOVES],
-    is_old: bool,
+    generation: u8,
+}
+
+impl TableData {
+    const USED_BIT: u64 = 1 << 56;
+
+    #[inline(always)]
+    fn pack(&self) -> u64 {
+        (self.max as u8 as u64)
+            | (self.min as u8 as u64) << 8
+            | (self.lv as u64) << 16
+            | (self.selectivity_lv as u64) << 24
+            | (self.moves[0] as u64) << 32
+            | (self.moves[1] as u64) << 40
+            | (self.generation as u64) << 48
+            | Self::USED_BIT
+    }
+
+    #[inline(always)]
+    fn unpack(data: u64) -> Self {
+        Self {
+            max: data as u8 as i8,
+            min: (data >> 8) as u8 as i8,
+            lv: (data >> 16) as u8,
+            selectivity_lv: (data >> 24) as u8,
+            moves: [(data >> 32) as u8, (data >> 40) as u8],
+            generation: (data >> 48) as u8,
+        }
+    }
+
+    /// 現在の世代 `generation` から見た、エントリの古さ (登録されてから何回探索したか)
+    ///
+    /// 世代は `u8` で一周するため、差を wrapping で求める。
+    #[inline(always)]
+    fn age(&self, generation: u8) -> u8 {
+        generation.wrapping_sub(self.generation)
+    }
+}
+
+/// 置換表の1エントリ
+///
+/// 複数スレッドから同時に読み書きできるように、盤面とデータを `AtomicU64` で保持する。
+/// 盤面はデータとの XOR で格納しておき、読み出し時に復元した盤面が一致しなければ、
+/// 書き込み途中のエントリを読んだとみなして無視する。(lockless hashing)
+#[derive(Default)]
+struct TableEntry {
+    player: AtomicU64,
+    opponent: AtomicU64,
+    data: AtomicU64,
+}
+
+impl TableEntry {
+    #[inline(always)]
+    fn load(&self) -> Option<(u64, u64, u64)> {
+        let data = self.data.load(Ordering::Relaxed);
+        if data & TableData::USED_BIT == 0 {
+            return None;
+        }
+        let player = self.player.load(Ordering::Relaxed) ^ data;
+        let opponent = self.opponent.load(Ordering::Relaxed) ^ data;
+        Some((player, opponent, data))
+    }
+
+    #[inline(always)]
+    fn store(&self, board: &Board, data: u64) {
+        self.player.store(board.player ^ data, Ordering::Relaxed);
+        self.opponent.store(board.opponent ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
 }
 
+/// 置換表
+///
+/// `add`, `get` は `&self` で呼び出せるため、`Arc` で包むことで複数の探索スレッドから共有できる。
+///
+/// エントリは `BUCKET_SIZE` 個ずつのバケットに分かれており、同じバケット内の
+/// エントリの中から、古い探索 (世代) のもの、浅い探索のものを優先して置き換える。
 pub struct TranspositionTable {
-    table: Vec<Option<TableData>>,
+    table: Vec<TableEntry>,
+    /// エントリ数 - 1 (エントリ数は 2 のべき乗)
+    mask: usize,
     rand_table: Box<[u32; 1 << 16]>,
+    /// 探索の世代 (`new_search` を呼ぶたびに増える)
+    generation: AtomicU8,
 }
 
 impl Default for TranspositionTable {
     fn default() -> Self {
-        let rand_table: Box<[u32; 1 << 16]> = Self::gen_rand_table();
-        Self {
-            table: vec![None; TABLE_SIZE],
-            rand_table,
-        }
+        Self::with_n_entries(DEFAULT_TABLE_SIZE)
     }
 }
 
@@ -34,6 +144,41 @@ impl TranspositionTable {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// メモリ使用量 (MB) を指定して置換表を生成する
+    ///
+    /// エントリ数は、指定したメモリ量に収まる最大の 2 のべき乗に切り下げる。
+    pub fn with_size_mb(size_mb: usize) -> Self {
+        let n_entries = (size_mb.saturating_mul(1 << 20) / std::mem::size_of::<TableEntry>())
+            .clamp(MIN_TABLE_SIZE, MAX_TABLE_SIZE);
+        let n_entries = if n_entries.is_power_of_two() {
+            n_entries
+        } else {
+            n_entries.next_power_of_two() >> 1
+        };
+        Self::with_n_entries(n_entries)
+    }
+
+    fn with_n_entries(n_entries: usize) -> Self {
+        debug_assert!(n_entries.is_power_of_two());
+        Self {
+            table: (0..n_entries).map(|_| TableEntry::default()).collect(),
+            mask: n_entries - 1,
+            rand_table: Self::gen_rand_table(),
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    /// エントリ数
+    pub fn n_entries(&self) -> usize {
+        self.table.len()
+    }
+
+    /// 置換表のメモリ使用量 (byte)
+    pub fn memory_size(&self) -> usize {
+        self.table.len() * std::mem::size_of::<TableEntry>()
+    }
+
     fn gen_rand_table() -> Box<[u32; 1 << 16]> {
         // let mut rng = rand::thread_rng();
         let seed: [u8; 32] = [13; 32];
@@ -42,7 +187,7 @@ impl TranspositionTable {
         let mut table = Box::new([0; 1 << 16]);
 
         for ti in table.iter_mut() {
-            *ti = rng.gen_range(0..TABLE_SIZE as u32);
+            *ti = rng.gen();
         }
 
         table
@@ -61,11 +206,12 @@ impl TranspositionTable {
             ^ self.rand_table[((opponent_board_bit >> 32) & 0xFFFF) as usize]
             ^ self.rand_table[((opponent_board_bit >> 16) & 0xFFFF) as usize]
             ^ self.rand_table[(opponent_board_bit & 0xFFFF) as usize]) as usize
+            & self.mask
     }
 
     #[inline(always)]
     pub fn add(
-        &mut self,
+        &self,
         board: &Board,
         min: i32,
         max: i32,
@@ -83,105 +229,320 @@ impl TranspositionTable {
             );
         }
 
-        let index = self.hash_board(board);
+        let bucket = self.bucket(board);
+        let generation = self.generation.load(Ordering::Relaxed);
 
-        // エントリの更新処理（共通）
-        let update_entry = |entry: &mut TableData| {
-            entry.max = max as i8;
-            entry.min = min as i8;
-            entry.lv = lv as u8;
-            entry.selectivity_lv = selectivity_lv as u8;
-            if entry.moves[0] != best_move && best_move != NO_COORD {
-                entry.moves[1] = entry.moves[0];
-                entry.moves[0] = best_move;
+        // 同じ局面のエントリがあれば更新する
+        for entry in bucket.iter() {
+            if let Some((p, o, data)) = entry.load() {
+                if p == board.player && o == board.opponent {
+                    let mut t = TableData::unpack(data);
+                    t.max = max as i8;
+                    t.min = min as i8;
+                    t.lv = lv as u8;
+                    t.selectivity_lv = selectivity_lv as u8;
+                    if t.moves[0] != best_move && best_move != NO_COORD {
+                        t.moves[1] = t.moves[0];
+                        t.moves[0] = best_move;
+                    }
+                    t.generation = generation;
+                    entry.store(board, t.pack());
+                    return;
+                }
             }
-            entry.is_old = false;
-        };
+        }
 
-        // 新規エントリ生成
-        let new_entry = || TableData {
-            board: board.clone(),
+        let entry = Self::replaced_entry(bucket, generation);
+        let t = TableData {
             max: max as i8,
             min: min as i8,
             lv: lv as u8,
             selectivity_lv: selectivity_lv as u8,
             moves: [best_move, NO_COORD],
-            is_old: false,
+            generation,
         };
+        entry.store(board, t.pack());
+    }
 
-        // 1. まず現在のハッシュ位置を確認
-        match self.table[index].as_mut() {
-            Some(entry)
-                if entry.board.player == board.player && entry.board.opponent == board.opponent =>
-            {
-                update_entry(entry);
-                return;
-            }
-            Some(entry) if entry.is_old => {
-                self.table[index] = Some(new_entry());
-                return;
-            }
-            None => {
-                self.table[index] = Some(new_entry());
-                return;
+    #[inline(always)]
+    pub fn get(&self, board: &Board) -> Option<TableData> {
+        self.bucket(board).iter().find_map(|entry| {
+            entry
+                .load()
+                .filter(|&(p, o, _)| p == board.player && o == board.opponent)
+                .map(|(_, _, data)| TableData::unpack(data))
+        })
+    }
+
+    /// バケット内で置き換えるエントリを選ぶ
+    ///
+    /// 空きエントリ、または最も価値の低いエントリを置き換える。
+    /// (古い世代のもの > 探索の浅いもの > 選択性の低いもの の順に置き換える)
+    /// 世代の新旧は、一周しても正しく比べられるように `TableData::age` で比べる。
+    #[inline(always)]
+    fn replaced_entry(bucket: &[TableEntry], generation: u8) -> &TableEntry {
+        let replace_priority = |entry: &TableEntry| match entry.load() {
+            None => (0, 0, 0, false),
+            Some((_, _, data)) => {
+                let t = TableData::unpack(data);
+                (u8::MAX - t.age(generation), t.lv, t.selectivity_lv, true)
             }
-            _ => {} // 存在はするが、ボードが異なる場合
+        };
+        bucket.iter().min_by_key(|e| replace_priority(e)).unwrap()
+    }
+
+    #[inline(always)]
+    fn bucket(&self, board: &Board) -> &[TableEntry] {
+        let index = self.hash_board(board) & !(BUCKET_SIZE - 1);
+        &self.table[index..index + BUCKET_SIZE]
+    }
+
+    /// 新しい探索を開始する
+    ///
+    /// 世代を進めることで、これまでに登録されたエントリを置き換えやすくする。
+    /// (エントリ自体は、置き換えられるまで引き続き参照できる)
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count_used_tt(&self) -> usize {
+        self.table.iter().filter(|e| e.load().is_some()).count()
+    }
+
+    /// 使用中のエントリの割合 (0.0 - 1.0)
+    pub fn fill_rate(&self) -> f64 {
+        self.count_used_tt() as f64 / self.table.len() as f64
+    }
+
+    /// 置換表をファイルに保存する
+    ///
+    /// 使用中のエントリのみを bincode で書き出す。
+    /// `evaluator` には、探索に使用した評価関数を指定する。
+    pub fn write_file(&self, path: &str, evaluator: &dyn Evaluate, mpc_table: &MpcTable) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let header = SnapshotHeader {
+            magic: SNAPSHOT_MAGIC,
+            version: SNAPSHOT_VERSION,
+            eval_version: evaluator.version().to_string(),
+            eval_checksum: evaluator.weights_checksum(),
+            mpc_table: mpc_table.clone(),
+            n_entries: self.count_used_tt() as u64,
+        };
+        header_options().serialize_into(&mut writer, &header).map_err(to_io_error)?;
+        for (player, opponent, data) in self.table.iter().filter_map(|e| e.load()) {
+            bincode::serialize_into(&mut writer, &(player, opponent, data)).map_err(to_io_error)?;
         }
+        writer.flush()
+    }
 
-        // 2. 次のインデックスを調べる
-        let index = (index + 1) % TABLE_SIZE;
-        match self.table[index].as_mut() {
-            Some(entry)
-                if entry.board.player == board.player && entry.board.opponent == board.opponent =>
-            {
-                update_entry(entry);
-            }
-            Some(entry) if entry.is_old => {
-                self.table[index] = Some(new_entry());
-            }
-            Some(entry)
-                if lv < entry.lv as i32
-                    || (lv == entry.lv as i32 && selectivity_lv < entry.selectivity_lv as i32) =>
-            {
-                // エントリが古くなく、かつ置換表の lv, selectivity_lv より劣るなら何もしない
-            }
-            _ => {
-                self.table[index] = Some(new_entry());
-            }
+    /// `write_file` で保存した置換表を読み込む
+    ///
+    /// 読み込んだエントリは、現在の世代のエントリとして登録する。
+    /// 置換表のサイズは保存時と異なっていてもよい (入りきらないエントリは置き換えられる)。
+    /// 形式や評価関数 (バージョンと重み)、MPC の表が一致しない場合は、`InvalidData` のエラーを返す。
+    /// エントリは全て読み込めた後に登録するため、エラーの場合は置換表を変更しない。
+    /// 戻り値は読み込んだエントリ数。
+    pub fn read_file(&self, path: &str, evaluator: &dyn Evaluate, mpc_table: &MpcTable) -> io::Result<usize> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let header: SnapshotHeader = header_options().deserialize_from(&mut reader).map_err(to_io_error)?;
+        if header.magic != SNAPSHOT_MAGIC || header.version != SNAPSHOT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a transposition table snapshot, or unsupported version",
+            ));
+        }
+        let eval_version = evaluator.version();
+        if header.eval_version != eval_version {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "transposition table snapshot was made with evaluator version {}, but {} is in use",
+                    header.eval_version, eval_version
+                ),
+            ));
         }
+        if header.eval_checksum != evaluator.weights_checksum() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "transposition table snapshot was made with different evaluator weights",
+            ));
+        }
+        if header.mpc_table != *mpc_table {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "transposition table snapshot was made with different MPC parameters",
+            ));
+        }
+
+        let entries = (0..header.n_entries)
+            .map(|_| bincode::deserialize_from::<_, (u64, u64, u64)>(&mut reader).map_err(to_io_error))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let generation = self.generation.load(Ordering::Relaxed);
+        for &(player, opponent, data) in entries.iter() {
+            let board = Board { player, opponent };
+            let mut t = TableData::unpack(data);
+            t.generation = generation;
+            Self::replaced_entry(self.bucket(&board), generation).store(&board, t.pack());
+        }
+        Ok(entries.len())
+    }
+}
+
+/// ヘッダの読み書きに用いる bincode の設定
+///
+/// エントリの形式 (`bincode::serialize_into`) と同じ固定長の整数で、サイズを制限する。
+fn header_options() -> impl Options {
+    bincode::options()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(SNAPSHOT_HEADER_LIMIT)
+}
+
+fn to_io_error(e: bincode::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::Evaluator;
+    use crate::mpc::{MpcParams, NO_MPC};
+
+    fn evaluator(version: &str) -> Evaluator {
+        let mut evaluator = Evaluator::zero();
+        evaluator.version = version.to_string();
+        evaluator
     }
 
-    #[inline(always)]
-    pub fn get(&self, board: &Board) -> Option<&TableData> {
-        let index = self.hash_board(board);
+    #[test]
+    fn snapshot_roundtrip() {
+        let table = TranspositionTable::with_size_mb(1);
+        let mut boards = Vec::new();
+        let mut board = Board::new();
+        for i in 0..20 {
+            let moves = board.moves();
+            if moves == 0 {
+                break;
+            }
+            let m = 1u64 << moves.trailing_zeros();
+            table.add(&board, -i * 2, i * 2, i, 3, position_bit_to_num(m).unwrap());
+            boards.push(board.clone());
+            board.put_piece_fast(m);
+        }
 
-        let mut td = self.table[index]
-            .as_ref()
-            .filter(|&x| x.board.player == board.player && x.board.opponent == board.opponent);
+        let path = std::env::temp_dir().join("deft_reversi_t_table_snapshot_roundtrip.bin");
+        let path = path.to_str().unwrap();
+        let mpc_table = MpcTable::default();
+        let eval_1 = evaluator("eval-1");
+        table.write_file(path, &eval_1, &mpc_table).unwrap();
 
-        if td.is_none() {
-            let index = (index + 1) % TABLE_SIZE;
-            td = self.table[index]
-                .as_ref()
-                .filter(|&x| x.board.player == board.player && x.board.opponent == board.opponent);
+        let loaded = TranspositionTable::with_size_mb(2);
+        assert_eq!(loaded.read_file(path, &eval_1, &mpc_table).unwrap(), boards.len());
+        for board in boards.iter() {
+            let (a, b) = (table.get(board).unwrap(), loaded.get(board).unwrap());
+            assert_eq!((a.min, a.max, a.lv, a.selectivity_lv, a.moves), (b.min, b.max, b.lv, b.selectivity_lv, b.moves));
         }
 
-        td
+        let stale = TranspositionTable::with_size_mb(1);
+        let err = stale.read_file(path, &evaluator("eval-2"), &mpc_table).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(stale.count_used_tt(), 0);
+
+        // バージョンが同じでも、重みが異なる場合は読み込まない
+        let mut retrained = evaluator("eval-1");
+        retrained.eval[0][0].const_eval = 1;
+        let err = stale.read_file(path, &retrained, &mpc_table).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(stale.count_used_tt(), 0);
+
+        // MPC のパラメータが異なる場合も読み込まない
+        let mut other_mpc_table = mpc_table.clone();
+        other_mpc_table.perfect_search[20] = Some(MpcParams { lv: 4, a: 1.0, b: 0.0, e_std: 5.0 });
+        let err = stale.read_file(path, &eval_1, &other_mpc_table).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(stale.count_used_tt(), 0);
+
+        // 途中で切れたファイルは、1エントリも登録しない
+        let bytes = std::fs::read(path).unwrap();
+        std::fs::write(path, &bytes[..bytes.len() - 10]).unwrap();
+        let err = stale.read_file(path, &eval_1, &mpc_table).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(stale.count_used_tt(), 0);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn snapshot_with_a_huge_declared_length_is_rejected() {
+        // 評価関数のバージョン文字列の長さが壊れたヘッダ
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&SNAPSHOT_MAGIC);
+        bytes.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(u64::MAX / 2).to_le_bytes());
+        bytes.extend_from_slice(b"eval-1");
+
+        let path = std::env::temp_dir().join("deft_reversi_t_table_snapshot_huge_length.bin");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, &bytes).unwrap();
+
+        let table = TranspositionTable::with_size_mb(1);
+        let err = table.read_file(path, &evaluator("eval-1"), &MpcTable::default()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(table.count_used_tt(), 0);
+
+        std::fs::remove_file(path).unwrap();
     }
 
-    pub fn set_old(&mut self) {
-        for t in self.table.iter_mut().flatten() {
-            t.is_old = true;
+    #[test]
+    fn size_in_mb_is_rounded_down_to_a_power_of_two() {
+        for size_mb in [1, 3, 16] {
+            let table = TranspositionTable::with_size_mb(size_mb);
+            assert!(table.n_entries().is_power_of_two());
+            assert_eq!(table.memory_size(), table.n_entries() * std::mem::size_of::<TableEntry>());
+            assert!(table.memory_size() <= size_mb << 20 && table.memory_size() * 2 > size_mb << 20);
         }
+        // 0 MB を指定しても、最小のサイズで作る
+        assert_eq!(TranspositionTable::with_size_mb(0).n_entries(), MIN_TABLE_SIZE);
+
+        let table = TranspositionTable::with_size_mb(1);
+        assert_eq!(table.fill_rate(), 0.0);
+        table.add(&Board::new(), -2, 2, 10, NO_MPC, 19);
+        assert_eq!(table.count_used_tt(), 1);
+        assert_eq!(table.fill_rate(), 1.0 / table.n_entries() as f64);
     }
 
-    pub fn count_used_tt(&self) -> usize {
-        let mut i = 0;
-        for e in self.table.iter() {
-            if e.is_some() {
-                i += 1;
-            }
+    #[test]
+    fn oldest_entry_is_replaced_across_the_generation_wrap() {
+        let store = |entry: &TableEntry, player: u64, lv: u8, generation: u8| {
+            let t = TableData { max: 0, min: 0, lv, selectivity_lv: NO_MPC as u8, moves: [NO_COORD; N_TT_MOVES], generation };
+            entry.store(&Board { player, opponent: 0 }, t.pack());
+        };
+        let bucket: [TableEntry; BUCKET_SIZE] = Default::default();
+        // 世代 250 は一周する前 (現在の世代 2 から見て 8 回前) の探索
+        store(&bucket[0], 1, 20, 1);
+        store(&bucket[1], 2, 30, 250);
+        store(&bucket[2], 3, 5, 2);
+        store(&bucket[3], 4, 10, 0);
+        assert_eq!(TableData::unpack(bucket[1].data.load(Ordering::Relaxed)).age(2), 8);
+        assert!(std::ptr::eq(TranspositionTable::replaced_entry(&bucket, 2), &bucket[1]));
+
+        // 古さが同じなら、浅い探索のものを置き換える
+        store(&bucket[1], 2, 30, 1);
+        store(&bucket[3], 4, 10, 2);
+        assert!(std::ptr::eq(TranspositionTable::replaced_entry(&bucket, 2), &bucket[0]));
+
+        // 256 回の探索で世代が一周しても、直前の探索のエントリを残す
+        let table = TranspositionTable::with_size_mb(1);
+        for _ in 0..256 {
+            table.new_search();
         }
-        i
+        let generation = table.generation.load(Ordering::Relaxed);
+        assert_eq!(generation, 0);
+        store(&bucket[0], 1, 20, generation.wrapping_sub(1));
+        store(&bucket[1], 2, 30, generation.wrapping_sub(2));
+        store(&bucket[2], 3, 5, generation);
+        store(&bucket[3], 4, 10, generation.wrapping_sub(3));
+        assert!(std::ptr::eq(TranspositionTable::replaced_entry(&bucket, generation), &bucket[3]));
     }
 }
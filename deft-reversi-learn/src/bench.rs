@@ -0,0 +1,122 @@
+// 評価関数の精度の測定 (評価値付きの局面に対する誤差)
+
+use serde::Serialize;
+
+use deft_reversi_engine::evaluator_const::N_PHASE;
+use deft_reversi_engine::*;
+
+use crate::record::TrainingPosition;
+
+/// 局面の集合に対する評価関数の誤差
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ErrorStats {
+    pub n_positions: usize,
+    /// 平均二乗誤差 (局面が無い場合は `None`)
+    pub mse: Option<f64>,
+    /// 平均絶対誤差
+    pub mae: Option<f64>,
+    /// 勝敗が決まっている (評価値が 0 でない) 局面の数
+    pub n_decided: usize,
+    /// 勝敗が決まっている局面のうち、評価関数の符号が一致した割合
+    pub sign_accuracy: Option<f64>,
+}
+
+/// 評価関数の精度 (`bench_evaluator`)
+#[derive(Clone, Debug, Serialize)]
+pub struct BenchResult {
+    pub eval_version: String,
+    pub total: ErrorStats,
+    /// フェーズ (`Evaluator::table_index`) ごとの誤差
+    pub phases: Vec<ErrorStats>,
+}
+
+/// 誤差の集計
+#[derive(Clone, Copy, Default)]
+struct ErrorSum {
+    n: usize,
+    squared: f64,
+    absolute: f64,
+    n_decided: usize,
+    n_sign_agreed: usize,
+}
+
+impl ErrorSum {
+    fn add(&mut self, eval: i32, score: i32) {
+        let diff = (eval - score) as f64;
+        self.n += 1;
+        self.squared += diff * diff;
+        self.absolute += diff.abs();
+        if score != 0 {
+            self.n_decided += 1;
+            if eval.signum() == score.signum() {
+                self.n_sign_agreed += 1;
+            }
+        }
+    }
+
+    fn merge(&mut self, other: &ErrorSum) {
+        self.n += other.n;
+        self.squared += other.squared;
+        self.absolute += other.absolute;
+        self.n_decided += other.n_decided;
+        self.n_sign_agreed += other.n_sign_agreed;
+    }
+
+    fn stats(&self) -> ErrorStats {
+        let ratio = |x: f64, n: usize| if n == 0 { None } else { Some(x / n as f64) };
+        ErrorStats {
+            n_positions: self.n,
+            mse: ratio(self.squared, self.n),
+            mae: ratio(self.absolute, self.n),
+            n_decided: self.n_decided,
+            sign_accuracy: ratio(self.n_sign_agreed as f64, self.n_decided),
+        }
+    }
+}
+
+/// 各局面を `Evaluator::clac_features_eval` で評価し、評価値との誤差を求める
+///
+/// 勝敗の一致は、評価値が 0 (引き分け) の局面を除いて数え、評価関数の値が 0 の場合は不一致とする。
+pub fn bench_evaluator(evaluator: &Evaluator, positions: &[TrainingPosition]) -> BenchResult {
+    let mut scratch = FeatureScratch::new(FeatureExtractor::default());
+    let mut phases = [ErrorSum::default(); N_PHASE];
+    for p in positions {
+        let eval = evaluator.clac_features_eval(&p.board, &mut scratch);
+        let (_, phase) = Evaluator::table_index(&p.board);
+        phases[phase].add(eval, p.score);
+    }
+
+    let mut total = ErrorSum::default();
+    for phase in phases.iter() {
+        total.merge(phase);
+    }
+
+    BenchResult {
+        eval_version: evaluator.version.clone(),
+        total: total.stats(),
+        phases: phases.iter().map(ErrorSum::stats).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::replay_record;
+
+    #[test]
+    fn zero_evaluator_error_is_the_score() {
+        let positions = replay_record("d3c3b3d2e1d6d7e3f4").unwrap().training_positions().unwrap();
+        let result = bench_evaluator(&Evaluator::zero(), &positions);
+
+        // 全ての局面の最終石差は ±64
+        assert_eq!(result.total.n_positions, positions.len());
+        assert_eq!(result.total.mse, Some(64.0 * 64.0));
+        assert_eq!(result.total.mae, Some(64.0));
+        assert_eq!(result.total.n_decided, positions.len());
+        assert_eq!(result.total.sign_accuracy, Some(0.0));
+
+        assert_eq!(result.phases.len(), N_PHASE);
+        assert_eq!(result.phases[0].n_positions, 2);
+        assert_eq!(result.phases[10].mse, None);
+    }
+}
@@ -0,0 +1,284 @@
+// 評価値付きの局面のデータセット (テキスト形式とバイナリ形式)
+//
+// テキスト形式: 1行に1局面を "<盤面> <手番> <phase> <評価値>" の形式で書く。
+//   盤面   : 64文字 (A1, B1, ..., H8 の順に 黒 'X', 白 'O', 空き '-'; 問題ファイル (.obf) と同じ)
+//   手番   : 'X' (黒) または 'O' (白)
+//   phase  : 評価値の表の添字 (`Evaluator::table_index`)
+//   評価値 : 手番側から見た石差
+//
+// バイナリ形式: 学習のたびに棋譜を再生しなくて済むよう、局面をそのまま並べる。
+//   (`to_binary` を参照)
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use deft_reversi_engine::*;
+
+use crate::record::TrainingPosition;
+
+/// 評価値付きの局面 (データセットの1行)
+#[derive(Clone)]
+pub struct LabelledPosition {
+    /// 手番側から見た盤面
+    pub board: Board,
+    pub turn: Color,
+    /// 手番側から見た石差
+    pub score: i32,
+}
+
+impl LabelledPosition {
+    pub fn phase(&self) -> usize {
+        Evaluator::table_index(&self.board).1
+    }
+
+    pub fn to_line(&self) -> String {
+        let (black, white) = match self.turn {
+            Color::Black => (self.board.player, self.board.opponent),
+            Color::White => (self.board.opponent, self.board.player),
+        };
+        let board: String = (0..64)
+            .map(|i| {
+                if black >> i & 1 == 1 {
+                    'X'
+                } else if white >> i & 1 == 1 {
+                    'O'
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+        format!("{} {} {} {}", board, self.turn.get_char(), self.phase(), self.score)
+    }
+
+    pub fn from_line(line: &str) -> Result<Self, &'static str> {
+        let mut fields = line.split_whitespace();
+        let board_str = fields.next().ok_or("missing board")?;
+        let turn_str = fields.next().ok_or("missing side to move")?;
+        let _phase = fields.next().ok_or("missing phase")?;
+        let score_str = fields.next().ok_or("missing score")?;
+
+        if board_str.len() != 64 {
+            return Err("the board must have 64 squares");
+        }
+        let mut board = Board { player: 0, opponent: 0 };
+        for (i, c) in board_str.chars().enumerate() {
+            match c {
+                'X' => board.player |= 1 << i,
+                'O' => board.opponent |= 1 << i,
+                '-' => (),
+                _ => return Err("invalid square"),
+            }
+        }
+        let turn = match turn_str {
+            "X" => Color::Black,
+            "O" => {
+                board.swap();
+                Color::White
+            }
+            _ => return Err("invalid side to move"),
+        };
+        let score = score_str.parse().map_err(|_| "invalid score")?;
+
+        Ok(Self { board, turn, score })
+    }
+}
+
+impl From<LabelledPosition> for TrainingPosition {
+    fn from(p: LabelledPosition) -> Self {
+        TrainingPosition { board: p.board, score: p.score }
+    }
+}
+
+/// テキスト形式のデータセットを読み込む (空行と `#` で始まる行は無視する)
+pub fn read_text_dataset(path: &Path) -> io::Result<Vec<LabelledPosition>> {
+    let contents = fs::read_to_string(path)?;
+    let mut positions = Vec::new();
+    for (line_no, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let position = LabelledPosition::from_line(line).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}:{}: {}", path.display(), line_no + 1, e))
+        })?;
+        positions.push(position);
+    }
+    Ok(positions)
+}
+
+/// データセットのバイナリファイルの識別子
+const BINARY_MAGIC: [u8; 4] = *b"DRDS";
+/// バイナリ形式のバージョン (形式を変えたら更新する)
+const BINARY_VERSION: u32 = 1;
+/// バイナリ形式の1局面の大きさ
+const BINARY_ROW_SIZE: usize = 17;
+
+/// データセットを読み込む
+///
+/// 先頭の識別子でバイナリ形式かテキスト形式かを判別する。
+pub fn read_dataset_file(path: &Path) -> io::Result<Vec<TrainingPosition>> {
+    let input = fs::read(path)?;
+    if input.starts_with(&BINARY_MAGIC) {
+        from_binary(&input).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))
+    } else {
+        Ok(read_text_dataset(path)?.into_iter().map(TrainingPosition::from).collect())
+    }
+}
+
+/// バイナリ形式で保存する
+pub fn write_binary_dataset(path: &Path, positions: &[TrainingPosition]) -> io::Result<()> {
+    fs::write(path, to_binary(positions))
+}
+
+/// バイナリ形式に変換する
+///
+/// 形式 (数値は全てリトルエンディアン):
+/// - 識別子 `DRDS`, 形式のバージョン (u32), 局面数 (u64)
+/// - 局面ごとに 手番側の石 (u64), 相手の石 (u64), 手番側から見た石差 (i8)
+pub fn to_binary(positions: &[TrainingPosition]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(16 + positions.len() * BINARY_ROW_SIZE);
+    out.extend_from_slice(&BINARY_MAGIC);
+    out.extend_from_slice(&BINARY_VERSION.to_le_bytes());
+    out.extend_from_slice(&(positions.len() as u64).to_le_bytes());
+    for p in positions {
+        out.extend_from_slice(&p.board.player.to_le_bytes());
+        out.extend_from_slice(&p.board.opponent.to_le_bytes());
+        out.push(p.score.clamp(-64, 64) as i8 as u8);
+    }
+    out
+}
+
+/// `to_binary` で変換したデータから局面を読み込む
+pub fn from_binary(input: &[u8]) -> Result<Vec<TrainingPosition>, &'static str> {
+    if input.len() < 16 || input[0..4] != BINARY_MAGIC {
+        return Err("not a dataset binary file");
+    }
+    let version = u32::from_le_bytes(input[4..8].try_into().unwrap());
+    if version != BINARY_VERSION {
+        return Err("unsupported dataset binary version");
+    }
+    let n_positions = u64::from_le_bytes(input[8..16].try_into().unwrap()) as usize;
+    let rows = &input[16..];
+    if rows.len() != n_positions.saturating_mul(BINARY_ROW_SIZE) {
+        return Err("dataset binary file is truncated");
+    }
+
+    Ok(rows
+        .chunks_exact(BINARY_ROW_SIZE)
+        .map(|row| TrainingPosition {
+            board: Board {
+                player: u64::from_le_bytes(row[0..8].try_into().unwrap()),
+                opponent: u64::from_le_bytes(row[8..16].try_into().unwrap()),
+            },
+            score: row[16] as i8 as i32,
+        })
+        .collect())
+}
+
+/// 対称な局面 (`Board::get_unique_board` が同じ局面) を1つにまとめる
+///
+/// まとめた局面は `get_unique_board` の向きで、評価値はそれらの平均 (四捨五入) とする。
+pub fn dedup_positions(positions: &[TrainingPosition]) -> Vec<TrainingPosition> {
+    // (局面, 評価値の和, 個数)
+    let mut unique: Vec<(Board, i64, i64)> = Vec::new();
+    let mut index: HashMap<(u64, u64), usize> = HashMap::new();
+    for p in positions {
+        let board = p.board.get_unique_board();
+        let i = *index.entry((board.player, board.opponent)).or_insert_with(|| {
+            unique.push((board, 0, 0));
+            unique.len() - 1
+        });
+        unique[i].1 += p.score as i64;
+        unique[i].2 += 1;
+    }
+
+    unique
+        .into_iter()
+        .map(|(board, sum, n)| TrainingPosition {
+            board,
+            score: ((sum as f64) / (n as f64)).round() as i32,
+        })
+        .collect()
+}
+
+/// 各局面を、盤面の対称性による8通りの局面に増やす (同じ局面になる向きは1つにする)
+pub fn augment_symmetries(positions: &[TrainingPosition]) -> Vec<TrainingPosition> {
+    let mut augmented = Vec::with_capacity(positions.len() * 8);
+    for p in positions {
+        let mut symmetries = p.board.all_symmetries();
+        symmetries.sort();
+        symmetries.dedup();
+        augmented.extend(symmetries.into_iter().map(|board| TrainingPosition { board, score: p.score }));
+    }
+    augmented
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::replay_record;
+
+    #[test]
+    fn line_roundtrip() {
+        let game = replay_record("f5d6c3d3c4f4f6f3e6e7").unwrap();
+        for (i, (board, turn)) in game.positions.into_iter().enumerate() {
+            let position = LabelledPosition { board, turn, score: i as i32 - 5 };
+            let line = position.to_line();
+            let parsed = LabelledPosition::from_line(&line).unwrap();
+
+            assert!(parsed.board == position.board);
+            assert_eq!(parsed.turn.get_char(), position.turn.get_char());
+            assert_eq!(parsed.score, position.score);
+            assert_eq!(parsed.to_line(), line);
+        }
+
+        let start = LabelledPosition { board: Board::new(), turn: Color::Black, score: 0 };
+        assert_eq!(
+            start.to_line(),
+            "---------------------------OX------XO--------------------------- X 0 0"
+        );
+        assert!(LabelledPosition::from_line("---- X 0 0").is_err());
+    }
+
+    #[test]
+    fn binary_roundtrip_dedup_and_augment() {
+        let positions: Vec<TrainingPosition> = replay_record("f5d6c3d3c4f4f6f3e6e7")
+            .unwrap()
+            .positions
+            .into_iter()
+            .enumerate()
+            .map(|(i, (board, _))| TrainingPosition { board, score: i as i32 - 5 })
+            .collect();
+
+        let binary = to_binary(&positions);
+        let restored = from_binary(&binary).unwrap();
+        assert_eq!(restored.len(), positions.len());
+        for (a, b) in restored.iter().zip(&positions) {
+            assert!(a.board == b.board);
+            assert_eq!(a.score, b.score);
+        }
+        assert!(from_binary(&binary[..binary.len() - 1]).is_err());
+
+        // 初手の4通りの着手は全て対称
+        let start = Board::new();
+        let first_moves: Vec<TrainingPosition> = MoveIterator::new(start.moves())
+            .map(|m| {
+                let mut board = start.clone();
+                board.put(m).unwrap();
+                TrainingPosition { board, score: m.trailing_zeros() as i32 % 2 }
+            })
+            .collect();
+        let unique = dedup_positions(&first_moves);
+        assert_eq!(unique.len(), 1);
+        assert_eq!(unique[0].score, 1); // (1 + 0 + 1 + 0) / 4 を四捨五入
+        assert!(unique[0].board == first_moves[0].board.get_unique_board());
+
+        let augmented = augment_symmetries(&unique);
+        assert_eq!(augmented.len(), 8);
+        // 初期局面は対角線と180度回転について対称なので、2通りしかない
+        assert_eq!(augment_symmetries(&[TrainingPosition { board: start, score: 0 }]).len(), 2);
+        assert_eq!(dedup_positions(&augmented).len(), 1);
+    }
+}
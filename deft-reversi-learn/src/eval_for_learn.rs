@@ -0,0 +1,182 @@
+// 学習用の評価関数 (重みを f64 で持つ)
+
+use deft_reversi_engine::evaluator_const::*;
+use deft_reversi_engine::*;
+
+/// 評価値の表 (`EvaluationScores` の f64 版, 単位は石差)
+#[derive(Clone)]
+pub struct EvaluationScoresForLearn {
+    pub pattern_eval: Vec<Vec<f64>>,
+    pub mobility_eval: Vec<f64>,
+    pub const_eval: f64,
+}
+
+/// 学習中の評価関数
+///
+/// 表の並びは `Evaluator` と同じ `eval[parity][phase]` で、
+/// 学習後に `SCORE_RATE` 倍して `Evaluator` に変換する。
+pub struct EvaluatorForLearn {
+    pub version: String,
+    pub n_deta_set: i32,
+    pub n_iteration: i32,
+    pub eval: Vec<Vec<EvaluationScoresForLearn>>,
+}
+
+impl Default for EvaluationScoresForLearn {
+    fn default() -> Self {
+        Self {
+            pattern_eval: N_FEATURE_POSITIONS.iter().map(|&n| vec![0.0; n]).collect(),
+            mobility_eval: vec![0.0; N_MOBILITY_MAX],
+            const_eval: 0.0,
+        }
+    }
+}
+
+impl Default for EvaluatorForLearn {
+    fn default() -> Self {
+        Self {
+            version: "0".to_string(),
+            n_deta_set: 0,
+            n_iteration: 0,
+            eval: vec![vec![EvaluationScoresForLearn::default(); N_PHASE]; 2],
+        }
+    }
+}
+
+impl EvaluatorForLearn {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 学習済みの評価関数から学習を再開する
+    pub fn from_evaluator(evaluator: &Evaluator) -> Self {
+        let to_f64 = |w: i16| w as f64 / SCORE_RATE as f64;
+        let eval = evaluator
+            .eval
+            .iter()
+            .map(|phases| {
+                phases
+                    .iter()
+                    .map(|e| EvaluationScoresForLearn {
+                        pattern_eval: e
+                            .pattern_eval
+                            .iter()
+                            .map(|p| p.iter().map(|&w| to_f64(w)).collect())
+                            .collect(),
+                        mobility_eval: e.mobility_eval.iter().map(|&w| to_f64(w)).collect(),
+                        const_eval: to_f64(e.const_eval),
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            version: evaluator.version.clone(),
+            n_deta_set: evaluator.n_deta_set,
+            n_iteration: evaluator.n_iteration,
+            eval,
+        }
+    }
+
+    /// 探索で使う評価関数 (`SCORE_RATE` 倍した i16) に変換する
+    pub fn to_evaluator(&self) -> Evaluator {
+        let to_i16 = |w: f64| (w * SCORE_RATE as f64).round() as i16;
+        let mut e = Evaluator::zero();
+        e.version = self.version.clone();
+        e.n_deta_set = self.n_deta_set;
+        e.n_iteration = self.n_iteration;
+
+        for (ei16_phases, ef64_phases) in e.eval.iter_mut().zip(&self.eval) {
+            for (ei16, ef64) in ei16_phases.iter_mut().zip(ef64_phases) {
+                for (ei16_p, ef64_p) in ei16.pattern_eval.iter_mut().zip(&ef64.pattern_eval) {
+                    for (ei16_f, &ef64_f) in ei16_p.iter_mut().zip(ef64_p) {
+                        *ei16_f = to_i16(ef64_f);
+                    }
+                }
+                for (ei16_m, &ef64_m) in ei16.mobility_eval.iter_mut().zip(&ef64.mobility_eval) {
+                    *ei16_m = to_i16(ef64_m);
+                }
+                ei16.const_eval = to_i16(ef64.const_eval);
+            }
+        }
+        e
+    }
+
+    /// 着手可能数の差の表の添字
+    #[inline(always)]
+    fn mobility_index(board: &Board) -> usize {
+        N_MOBILITY_BASE + board.moves().count_ones() as usize - board.opponent_moves().count_ones() as usize
+    }
+
+    /// 手番側から見た評価値 (石差, 丸めない)
+    pub fn clac_eval(&self, board: &Board, feature_bit: &FeatureBit) -> f64 {
+        let (parity, phase) = Evaluator::table_index(board);
+        let eval_scores = &self.eval[parity][phase];
+
+        let mut evaluation = 0.0;
+        for (e, f) in eval_scores.pattern_eval.iter().zip(feature_bit) {
+            // for each rotaion
+            evaluation += e[f[0] as usize] + e[f[1] as usize] + e[f[2] as usize] + e[f[3] as usize];
+        }
+        evaluation += eval_scores.mobility_eval[Self::mobility_index(board)];
+        evaluation += eval_scores.const_eval;
+
+        evaluation
+    }
+
+    /// 二乗誤差 (評価値 - 正解)^2 を減らす方向に、局面で使われた重みを更新する (確率的勾配降下法)
+    ///
+    /// `diff` は 評価値 - 正解。`lambda` は L2 正則化の係数。
+    pub fn update(&mut self, board: &Board, feature_bit: &FeatureBit, diff: f64, learning_rate: f64, lambda: f64) {
+        let (parity, phase) = Evaluator::table_index(board);
+        let eval_scores = &mut self.eval[parity][phase];
+        let step = |w: &mut f64| *w -= 2.0 * diff * learning_rate + 2.0 * lambda * *w;
+
+        for (e, f) in eval_scores.pattern_eval.iter_mut().zip(feature_bit) {
+            // each rotation boards
+            for &feature in f {
+                step(&mut e[feature as usize]);
+            }
+        }
+        step(&mut eval_scores.mobility_eval[Self::mobility_index(board)]);
+        step(&mut eval_scores.const_eval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prelude::*;
+
+    #[test]
+    fn converted_evaluator_matches_learned_weights() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut model = EvaluatorForLearn::new();
+        for scores in model.eval.iter_mut().flatten() {
+            for w in scores.pattern_eval.iter_mut().flatten() {
+                *w = rng.gen_range(-0.5..0.5);
+            }
+            for w in scores.mobility_eval.iter_mut() {
+                *w = rng.gen_range(-2.0..2.0);
+            }
+            scores.const_eval = rng.gen_range(-2.0..2.0);
+        }
+        let evaluator = model.to_evaluator();
+        let restored = EvaluatorForLearn::from_evaluator(&evaluator);
+        let mut scratch = FeatureScratch::new(FeatureExtractor::default());
+
+        let mut board = Board::new();
+        while board.moves() != 0 {
+            scratch.clac_features(&board);
+            let expected = model.clac_eval(&board, &scratch.feature_bit) * SCORE_RATE as f64;
+            let actual = evaluator.calc_eval_from_features(&board, &scratch.feature_bit) as f64;
+            // 46 個の重みの丸め誤差
+            assert!((expected - actual).abs() <= 23.0, "{expected} {actual}");
+
+            let e = restored.clac_eval(&board, &scratch.feature_bit) * SCORE_RATE as f64;
+            assert!((e - actual).abs() < 1e-6);
+
+            board.put(board.moves() & board.moves().wrapping_neg()).unwrap();
+        }
+    }
+}
@@ -0,0 +1,89 @@
+// 探索による学習局面の評価値付け
+
+use deft_reversi_engine::*;
+
+use crate::dataset::LabelledPosition;
+use crate::record::ReplayedGame;
+
+/// 評価値付けの設定
+#[derive(Clone, Debug)]
+pub struct LabelConfig {
+    /// 空きマス数がこれ以下の局面は完全読みの石差を付ける
+    pub exact_empties: i32,
+    /// それより前の局面を評価関数で探索する深さ
+    pub depth: i32,
+}
+
+impl Default for LabelConfig {
+    fn default() -> Self {
+        Self { exact_empties: 20, depth: 10 }
+    }
+}
+
+impl LabelConfig {
+    /// 評価関数による探索で評価値を付ける局面があるかどうか
+    ///
+    /// 棋譜の最初の局面は空きマスが 60 なので、`exact_empties` が 60 以上なら全て完全読みになる。
+    pub fn needs_evaluator(&self) -> bool {
+        self.exact_empties < 60
+    }
+
+    /// 局面の評価値を求める探索の種類 (どちらも MPC を使わない)
+    pub fn solver_type(&self, board: &Board) -> SolverType {
+        if board.empties_count() <= self.exact_empties {
+            SolverType::Perfect(NO_MPC)
+        } else {
+            SolverType::Eval(self.depth, NO_MPC)
+        }
+    }
+}
+
+/// 試合の全ての局面を探索し、手番側から見た評価値を付ける
+///
+/// 終局していない棋譜でもよい。
+pub fn label_game(solver: &mut Solver, game: &ReplayedGame, config: &LabelConfig) -> Vec<LabelledPosition> {
+    game.positions
+        .iter()
+        .map(|(board, turn)| {
+            let score = solver.solve_score_with_type(board, config.solver_type(board));
+            LabelledPosition { board: board.clone(), turn: *turn, score }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::replay_record;
+
+    #[test]
+    fn exact_labels_are_consistent_with_the_game() {
+        let record = "e6d6c6f6f5f4f3f2g7b7f1g2h2h1c3e2d2d1d3h3c4c2b1b2a1a2c1b3e1e3g1b5a3a4g3g4b4g6h4c5a5h5g5a6b6h6a7f8c7a8d7e7b8c8h8g8f7d8e8h7";
+        let game = replay_record(record).unwrap();
+        let score_black = game.score_black.unwrap();
+
+        let mut solver = Solver::new(Evaluator::zero());
+        let config = LabelConfig { exact_empties: 10, depth: 2 };
+        let labels = label_game(&mut solver, &game, &config);
+        assert_eq!(labels.len(), game.positions.len());
+
+        let for_black = |p: &LabelledPosition| if matches!(p.turn, Color::Black) { p.score } else { -p.score };
+        for pair in labels.windows(2) {
+            let (p, next) = (&pair[0], &pair[1]);
+            if p.board.empties_count() <= config.exact_empties {
+                // 完全読みの値は、実際に打った手の結果以上
+                let same_side = p.turn.get_char() == next.turn.get_char();
+                assert!(p.score >= if same_side { next.score } else { -next.score });
+            }
+        }
+        // 最後の局面 (1マス) は最終石差と一致する
+        assert_eq!(for_black(labels.last().unwrap()), score_black);
+    }
+
+    #[test]
+    fn evaluator_is_needed_unless_every_position_is_solved() {
+        assert!(LabelConfig::default().needs_evaluator());
+        assert!(LabelConfig { exact_empties: 59, depth: 10 }.needs_evaluator());
+        assert!(!LabelConfig { exact_empties: 60, depth: 10 }.needs_evaluator());
+    }
+}
@@ -1,3 +1,445 @@
-fn main() {
-    println!("Hello, world!");
+mod bench;
+mod dataset;
+mod eval_for_learn;
+mod label;
+mod mpc;
+mod record;
+mod train;
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::time::Instant;
+
+use clap::{Args, Parser, Subcommand};
+use deft_reversi_engine::{Evaluator, MpcTable, SimpleEvaluator, Solver};
+
+use crate::bench::*;
+use crate::dataset::*;
+use crate::eval_for_learn::EvaluatorForLearn;
+use crate::label::*;
+use crate::mpc::*;
+use crate::record::*;
+use crate::train::*;
+
+/// Training tools for the Deft Reversi evaluation function
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Fit the evaluation weights to the final scores of game records (or to labelled datasets)
+    Train(TrainArgs),
+    /// Label the positions of game records by searching them (exact scores near the end)
+    Label(LabelArgs),
+    /// Convert game records and datasets into the compact binary dataset format
+    Convert(ConvertArgs),
+    /// Measure the error of evaluators on labelled datasets, per phase
+    Bench(BenchArgs),
+    /// Estimate the MPC (ProbCut) parameters by comparing shallow and deep searches
+    Mpc(MpcArgs),
+}
+
+#[derive(Args, Debug)]
+struct TrainArgs {
+    /// Game record files (one game per line, e.g. "f5d6c3...") or directories containing them
+    #[arg(required_unless_present = "dataset")]
+    records: Vec<PathBuf>,
+
+    /// Dataset files written by the "label" or "convert" command (or directories containing them)
+    #[arg(short, long)]
+    dataset: Vec<PathBuf>,
+
+    /// Output path of the evaluator
+    /// (JSON if the path ends with ".json", the compact binary format otherwise)
+    #[arg(short, long, default_value = "eval.json")]
+    out: String,
+
+    /// Evaluator (JSON or binary) to continue training from (default: all weights zero)
+    #[arg(short, long)]
+    init: Option<String>,
+
+    /// Version string written to the evaluator
+    #[arg(long)]
+    eval_version: Option<String>,
+
+    /// Number of passes over the training positions
+    #[arg(long, default_value_t = TrainConfig::default().n_epochs)]
+    epochs: usize,
+
+    #[arg(long, default_value_t = TrainConfig::default().learning_rate)]
+    learning_rate: f64,
+
+    /// L2 regularization
+    #[arg(long, default_value_t = TrainConfig::default().lambda)]
+    lambda: f64,
+}
+
+#[derive(Args, Debug)]
+struct ConvertArgs {
+    /// Game record files (labelled with the final disc difference) or directories containing them
+    #[arg(required_unless_present = "dataset")]
+    records: Vec<PathBuf>,
+
+    /// Dataset files (text or binary) to include, or directories containing them
+    #[arg(short, long)]
+    dataset: Vec<PathBuf>,
+
+    /// Output path of the binary dataset
+    #[arg(short, long, default_value = "dataset.bin")]
+    out: PathBuf,
+
+    /// Merge symmetric duplicates of a position (averaging their scores)
+    #[arg(long)]
+    dedup: bool,
+
+    /// Add the 8 symmetric variants of every position (after --dedup)
+    #[arg(long)]
+    augment: bool,
+}
+
+#[derive(Args, Debug)]
+struct BenchArgs {
+    /// Dataset files (text or binary) or directories containing them
+    #[arg(required = true)]
+    dataset: Vec<PathBuf>,
+
+    /// Evaluators (JSON or binary) to measure (can be given several times)
+    #[arg(short, long, required = true)]
+    eval_path: Vec<String>,
+
+    /// Print one JSON object per evaluator (one per line) instead of a table
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Args, Debug)]
+struct LabelArgs {
+    /// Game record files (one game per line, e.g. "f5d6c3...") or directories containing them
+    #[arg(required = true)]
+    records: Vec<PathBuf>,
+
+    /// Output path of the labelled dataset
+    #[arg(short, long, default_value = "dataset.txt")]
+    out: PathBuf,
+
+    /// Evaluator (JSON or binary) used by the depth search (required unless --exact-empties is 60)
+    #[arg(short, long)]
+    eval_path: Option<String>,
+
+    /// Positions with at most this many empty squares are solved exactly
+    #[arg(long, default_value_t = LabelConfig::default().exact_empties)]
+    exact_empties: i32,
+
+    /// Search depth for the earlier positions
+    #[arg(long, default_value_t = LabelConfig::default().depth)]
+    depth: i32,
+
+    /// Number of search threads
+    #[arg(short, long, default_value_t = 1)]
+    threads: usize,
+
+    /// Transposition table size in MB
+    #[arg(long, id = "MB", default_value_t = 96)]
+    hash: usize,
+}
+
+#[derive(Args, Debug)]
+struct MpcArgs {
+    /// Game record files (one game per line, e.g. "f5d6c3...") or directories containing them
+    #[arg(required_unless_present = "dataset")]
+    records: Vec<PathBuf>,
+
+    /// Dataset files (text or binary) to sample positions from, or directories containing them
+    #[arg(short, long)]
+    dataset: Vec<PathBuf>,
+
+    /// Evaluator (JSON or binary) used by the search
+    #[arg(short, long)]
+    eval_path: String,
+
+    /// MPC table to start from (default: the compiled-in parameters).
+    /// Parameters without enough samples are kept from it
+    #[arg(short, long)]
+    init: Option<String>,
+
+    /// Output path of the MPC table (JSON)
+    #[arg(short, long, default_value = "mpc.json")]
+    out: String,
+
+    /// Number of positions searched per number of empty squares
+    #[arg(long, default_value_t = MpcConfig::default().n_samples)]
+    samples: usize,
+
+    /// Smallest search depth whose parameters are estimated
+    #[arg(long, default_value_t = MpcConfig::default().min_level)]
+    min_level: i32,
+
+    /// Largest search depth whose parameters are estimated
+    #[arg(long, default_value_t = MpcConfig::default().max_level)]
+    max_level: i32,
+
+    /// Estimate the endgame parameters up to this many empty squares (positions are solved exactly)
+    #[arg(long, default_value_t = MpcConfig::default().perfect_max_empties)]
+    perfect_max_empties: i32,
+
+    /// Number of search threads
+    #[arg(short, long, default_value_t = 1)]
+    threads: usize,
+
+    /// Transposition table size in MB
+    #[arg(long, id = "MB", default_value_t = 96)]
+    hash: usize,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Command::Train(args) => run_train(args),
+        Command::Label(args) => run_label(args),
+        Command::Convert(args) => run_convert(args),
+        Command::Bench(args) => run_bench(args),
+        Command::Mpc(args) => run_mpc(args),
+    }
+}
+
+/// 棋譜 (またはデータセット) を読み込んで評価関数を学習し、`args.out` に保存する
+fn run_train(args: TrainArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let mut positions = load_positions(&args.records, &args.dataset)?;
+    if positions.is_empty() {
+        return Err("no training positions".into());
+    }
+
+    let mut evaluator = match &args.init {
+        Some(path) => EvaluatorForLearn::from_evaluator(&Evaluator::read_file(path)?),
+        None => EvaluatorForLearn::new(),
+    };
+    if let Some(version) = args.eval_version {
+        evaluator.version = version;
+    }
+    evaluator.n_deta_set = positions.len() as i32;
+
+    let config = TrainConfig {
+        n_epochs: args.epochs,
+        learning_rate: args.learning_rate,
+        lambda: args.lambda,
+        ..TrainConfig::default()
+    };
+    train(&mut evaluator, &mut positions, &config, |epoch, error| {
+        println!(
+            "epoch: {epoch}, 10: {:.4}, 20: {:.4}, 30: {:.4}, 40: {:.4}, 50: {:.4}, 55: {:.4}, all: {:.4}",
+            error.mse[10], error.mse[20], error.mse[30], error.mse[40], error.mse[50], error.mse[55], error.total()
+        );
+    });
+
+    let evaluator = evaluator.to_evaluator();
+    if args.out.ends_with(".json") {
+        evaluator.write_file(&args.out)?;
+    } else {
+        evaluator.write_binary_file(&args.out)?;
+    }
+    println!("Saved evaluator (version {}) : {}", evaluator.version, args.out);
+
+    Ok(())
+}
+
+/// 棋譜の全ての局面を探索して評価値を付け、データセットとして `args.out` に保存する
+fn run_label(args: LabelArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let config = LabelConfig {
+        exact_empties: args.exact_empties,
+        depth: args.depth,
+    };
+    let mut solver = match &args.eval_path {
+        Some(path) => Solver::with_t_table_size_mb(Evaluator::read_file(path)?, args.hash),
+        // 完全読みだけなら、評価関数は手の並べ替えにしか使われない
+        None if !config.needs_evaluator() => Solver::with_t_table_size_mb(SimpleEvaluator, args.hash),
+        None => {
+            return Err(format!(
+                "positions with more than {} empty squares are labelled by a depth {} search, which needs an evaluator: \
+                 pass -e <eval.json|eval.bin> (or --exact-empties 60 to solve every position exactly)",
+                config.exact_empties, config.depth
+            )
+            .into())
+        }
+    };
+    solver.set_n_threads(args.threads);
+
+    let files = collect_files(&args.records)?;
+    let mut out = BufWriter::new(File::create(&args.out)?);
+    writeln!(out, "# exact empties: {}, depth: {}", config.exact_empties, config.depth)?;
+
+    let start = Instant::now();
+    let mut n_games = 0;
+    let mut n_skipped = 0;
+    let mut n_positions = 0;
+    for file in files.iter() {
+        for game in read_record_file(file, &mut n_skipped)? {
+            for position in label_game(&mut solver, &game, &config) {
+                writeln!(out, "{}", position.to_line())?;
+                n_positions += 1;
+            }
+            n_games += 1;
+            if n_games % 100 == 0 {
+                eprintln!("{} games, {} positions ({:.1}s)", n_games, n_positions, start.elapsed().as_secs_f64());
+            }
+        }
+    }
+    out.flush()?;
+
+    println!("Games     : {} ({} skipped)", n_games, n_skipped);
+    println!("Positions : {}", n_positions);
+    println!("Time      : {:.1}s", start.elapsed().as_secs_f64());
+    println!("Saved dataset : {}", args.out.display());
+
+    Ok(())
+}
+
+/// 棋譜 (最終石差を評価値とする) とデータセットから学習局面を読み込む
+fn load_positions(records: &[PathBuf], datasets: &[PathBuf]) -> Result<Vec<TrainingPosition>, Box<dyn std::error::Error>> {
+    let files = collect_files(records)?;
+    let mut n_games: usize = 0;
+    let mut n_skipped = 0;
+    let mut positions = Vec::new();
+    for file in files.iter() {
+        for game in read_record_file(file, &mut n_skipped)? {
+            match game.training_positions() {
+                Ok(game_positions) => {
+                    n_games += 1;
+                    positions.extend(game_positions);
+                }
+                // 終局していない棋譜は最終石差が分からない
+                Err(_) => n_skipped += 1,
+            }
+        }
+    }
+    let dataset_files = collect_files(datasets)?;
+    for file in dataset_files.iter() {
+        positions.extend(read_dataset_file(file)?);
+    }
+    println!("Record files : {}", files.len());
+    println!("Games        : {} ({} skipped)", n_games, n_skipped);
+    println!("Datasets     : {}", dataset_files.len());
+    println!("Positions    : {}", positions.len());
+    Ok(positions)
+}
+
+/// 棋譜とデータセットを読み込み、バイナリ形式のデータセットとして `args.out` に保存する
+fn run_convert(args: ConvertArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let mut positions = load_positions(&args.records, &args.dataset)?;
+    if args.dedup {
+        positions = dedup_positions(&positions);
+        println!("Unique       : {}", positions.len());
+    }
+    if args.augment {
+        positions = augment_symmetries(&positions);
+        println!("Augmented    : {}", positions.len());
+    }
+
+    write_binary_dataset(&args.out, &positions)?;
+    println!("Saved dataset : {}", args.out.display());
+    Ok(())
+}
+
+/// データセットに対する評価関数の誤差を、フェーズごとに表示する
+fn run_bench(args: BenchArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let mut positions = Vec::new();
+    for file in collect_files(&args.dataset)?.iter() {
+        positions.extend(read_dataset_file(file)?);
+    }
+    if positions.is_empty() {
+        return Err("no positions in the dataset".into());
+    }
+
+    for path in args.eval_path.iter() {
+        let evaluator = Evaluator::read_file(path)?;
+        let result = bench_evaluator(&evaluator, &positions);
+
+        if args.json {
+            let mut value = serde_json::to_value(&result)?;
+            value["eval_path"] = path.as_str().into();
+            println!("{}", value);
+            continue;
+        }
+
+        let fmt = |x: Option<f64>, precision: usize| x.map_or("-".to_string(), |x| format!("{:.*}", precision, x));
+        let print_row = |label: &str, stats: &ErrorStats| {
+            println!(
+                "{:>5} {:>10} {:>10} {:>8} {:>7}",
+                label,
+                stats.n_positions,
+                fmt(stats.mse, 2),
+                fmt(stats.mae, 2),
+                fmt(stats.sign_accuracy.map(|a| a * 100.0), 1)
+            );
+        };
+        println!("Evaluator : {} (version {})", path, result.eval_version);
+        println!("{:>5} {:>10} {:>10} {:>8} {:>7}", "phase", "positions", "MSE", "MAE", "sign%");
+        for (phase, stats) in result.phases.iter().enumerate() {
+            print_row(&phase.to_string(), stats);
+        }
+        print_row("all", &result.total);
+        println!();
+    }
+
+    Ok(())
+}
+
+/// 局面を探索して MPC のパラメータを求め、パラメータ表を `args.out` に保存する
+fn run_mpc(args: MpcArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let config = MpcConfig {
+        n_samples: args.samples,
+        min_level: args.min_level.max(1),
+        max_level: args.max_level.min(59),
+        perfect_max_empties: args.perfect_max_empties,
+    };
+    let init = match &args.init {
+        Some(path) => MpcTable::read_file(path)?,
+        None => MpcTable::default(),
+    };
+
+    let positions = load_positions(&args.records, &args.dataset)?;
+    let boards = sample_boards(&positions, config.n_samples);
+    if boards.is_empty() {
+        return Err("no positions to search".into());
+    }
+    println!("Sampled      : {}", boards.len());
+
+    let mut solver = Solver::with_t_table_size_mb(Evaluator::read_file(&args.eval_path)?, args.hash);
+    solver.set_n_threads(args.threads);
+
+    let start = Instant::now();
+    let samples = collect_samples(&mut solver, &boards, &init, &config, |n| {
+        if n % 100 == 0 {
+            eprintln!("{} / {} positions ({:.1}s)", n, boards.len(), start.elapsed().as_secs_f64());
+        }
+    });
+
+    // 完全読みの MPC: 深い探索 (deep) は完全読み、浅い探索 (shallow) はその深さの評価関数による探索
+    println!("{:>7} {:>4} {:>7} {:>5} {:>8} {:>8} {:>8}", "empties", "deep", "shallow", "n", "a", "b", "e_std");
+    for (n_empties, deep, shallow, fit) in fit_groups(&samples.perfect_search) {
+        println!(
+            "{:>7} {:>4} {:>7} {:>5} {:>8.4} {:>8.4} {:>8.4}",
+            n_empties, deep, shallow, fit.n, fit.a, fit.b, fit.e_std
+        );
+    }
+
+    let table = calibrate(&init, &samples);
+    if table.eval_search == init.eval_search {
+        println!("Eval search  : no usable fit (kept the initial parameters)");
+    } else {
+        println!("Eval search  : {} pairs of searches", samples.eval_search.len());
+        println!("  a     = {:?}", table.eval_search.a);
+        println!("  b     = {:?}", table.eval_search.b);
+        println!("  e_std = {:?}", table.eval_search.e_std);
+    }
+    println!("Time         : {:.1}s", start.elapsed().as_secs_f64());
+
+    table.write_file(&args.out)?;
+    println!("Saved MPC table : {}", args.out);
+    Ok(())
 }
@@ -0,0 +1,352 @@
+// MPC (Multi-ProbCut) のパラメータの推定
+//
+// 学習局面を探索し、浅い探索の値 v と深い探索の値 V の組を記録する。
+// それらに V = a v + b をあてはめ、残差の標準偏差を e_std とする。
+// 評価関数による探索は、(空きマス数, 深さ) ごとに求めたパラメータをさらに1次式 (`EvalSearchMpcModel`) で近似する。
+
+use rand::prelude::*;
+
+use deft_reversi_engine::*;
+
+use crate::record::TrainingPosition;
+
+/// パラメータの推定の設定
+#[derive(Clone, Debug)]
+pub struct MpcConfig {
+    /// 空きマス数ごとに探索する局面数
+    pub n_samples: usize,
+    /// 評価関数による探索の深さの範囲
+    pub min_level: i32,
+    pub max_level: i32,
+    /// 完全読みのパラメータを求める空きマス数の上限
+    pub perfect_max_empties: i32,
+}
+
+impl Default for MpcConfig {
+    fn default() -> Self {
+        Self {
+            n_samples: 64,
+            min_level: MPC_START_LEVEL_EVAL_SEARCH,
+            max_level: 10,
+            perfect_max_empties: 18,
+        }
+    }
+}
+
+/// 直線をあてはめるのに必要な、1つの組の最小の局面数
+pub const MIN_GROUP_SAMPLES: usize = 8;
+
+/// 浅い探索と深い探索の値の組
+#[derive(Clone, Copy, Debug)]
+pub struct MpcSample {
+    pub n_empties: i32,
+    /// 深い探索の深さ (完全読みでは空きマス数)
+    pub lv: i32,
+    /// 浅い探索の深さ
+    pub mpc_lv: i32,
+    pub deep: i32,
+    pub shallow: i32,
+}
+
+#[derive(Default)]
+pub struct MpcSamples {
+    pub eval_search: Vec<MpcSample>,
+    pub perfect_search: Vec<MpcSample>,
+}
+
+/// 学習局面から、空きマス数ごとに最大 `n_per_empties` 局面を無作為に選ぶ
+///
+/// 着手できない局面 (パス) は除く。
+pub fn sample_boards(positions: &[TrainingPosition], n_per_empties: usize) -> Vec<Board> {
+    let mut by_empties: Vec<Vec<&Board>> = vec![Vec::new(); 61];
+    for p in positions {
+        if p.board.moves() != 0 {
+            by_empties[p.board.empties_count() as usize].push(&p.board);
+        }
+    }
+
+    let mut rng = thread_rng();
+    by_empties
+        .iter()
+        .flat_map(|boards| boards.choose_multiple(&mut rng, n_per_empties).map(|&b| b.clone()))
+        .collect()
+}
+
+/// 局面を MPC を使わずに探索し、深さ (0 は評価関数の値) ごとの値を記録する
+struct SearchValues<'a> {
+    solver: &'a mut Solver,
+    board: &'a Board,
+    values: Vec<Option<i32>>,
+}
+
+impl SearchValues<'_> {
+    fn get(&mut self, depth: i32) -> i32 {
+        if let Some(v) = self.values[depth as usize] {
+            return v;
+        }
+        let v = if depth == 0 {
+            let search = &mut self.solver.search;
+            search.eval_func.evaluate(self.board, &mut search.eval_scratch)
+        } else {
+            self.solver.solve_score_with_type(self.board, SolverType::Eval(depth, NO_MPC))
+        };
+        self.values[depth as usize] = Some(v);
+        v
+    }
+}
+
+/// 各局面について、MPC で使う浅い探索と深い探索の値の組を求める
+///
+/// - 評価関数による探索: 深さ `lv` (`config.min_level..=config.max_level`, 空きマス数未満) と
+///   `EVAL_SEARCH_MPC_SEARCH_LV[lv]` の組
+/// - 完全読み: 空きマス数が `config.perfect_max_empties` 以下で `table` にパラメータがある局面について、
+///   完全読みとそのパラメータの浅い探索の深さの組
+///
+/// 局面を1つ探索するたびに `on_board` を呼ぶ (進捗の表示用)。
+pub fn collect_samples<F>(
+    solver: &mut Solver,
+    boards: &[Board],
+    table: &MpcTable,
+    config: &MpcConfig,
+    mut on_board: F,
+) -> MpcSamples
+where
+    F: FnMut(usize),
+{
+    let mut samples = MpcSamples::default();
+    for (i, board) in boards.iter().enumerate() {
+        let n_empties = board.empties_count();
+        let mut values = SearchValues { solver: &mut *solver, board, values: vec![None; 61] };
+
+        for lv in config.min_level..=config.max_level.min(n_empties - 1) {
+            let mpc_lv = EVAL_SEARCH_MPC_SEARCH_LV[lv as usize];
+            samples.eval_search.push(MpcSample {
+                n_empties,
+                lv,
+                mpc_lv,
+                deep: values.get(lv),
+                shallow: values.get(mpc_lv),
+            });
+        }
+
+        if n_empties <= config.perfect_max_empties {
+            if let Some(params) = table.perfect_search[n_empties as usize] {
+                let deep = values.solver.solve_score_with_type(board, SolverType::Perfect(NO_MPC));
+                samples.perfect_search.push(MpcSample {
+                    n_empties,
+                    lv: n_empties,
+                    mpc_lv: params.lv,
+                    deep,
+                    shallow: values.get(params.lv),
+                });
+            }
+        }
+        on_board(i + 1);
+    }
+    samples
+}
+
+/// 直線 V = a v + b のあてはめの結果
+#[derive(Clone, Copy, Debug)]
+pub struct LineFit {
+    pub n: usize,
+    pub a: f64,
+    pub b: f64,
+    /// 残差の標準偏差 (自由度 n - 2)
+    pub e_std: f64,
+}
+
+/// (v, V) の組に最小二乗法で直線をあてはめる
+///
+/// 点が `MIN_GROUP_SAMPLES` 未満か、v が全て同じ値の場合は `None` を返す。
+pub fn fit_line(points: &[(f64, f64)]) -> Option<LineFit> {
+    let n = points.len();
+    if n < MIN_GROUP_SAMPLES {
+        return None;
+    }
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n as f64;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n as f64;
+    let sxx: f64 = points.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+    let sxy: f64 = points.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+    if sxx == 0.0 {
+        return None;
+    }
+
+    let a = sxy / sxx;
+    let b = mean_y - a * mean_x;
+    let sse: f64 = points.iter().map(|p| (p.1 - a * p.0 - b).powi(2)).sum();
+    Some(LineFit { n, a, b, e_std: (sse / (n - 2) as f64).sqrt() })
+}
+
+/// 連立1次方程式 `m x = y` を解く (部分ピボット選択付きのガウスの消去法)
+fn solve_linear_system(mut m: [[f64; 4]; 4], mut y: [f64; 4]) -> Option<[f64; 4]> {
+    for col in 0..4 {
+        let pivot = (col..4).max_by(|&i, &j| m[i][col].abs().total_cmp(&m[j][col].abs())).unwrap();
+        if m[pivot][col].abs() < 1e-9 {
+            return None;
+        }
+        m.swap(col, pivot);
+        y.swap(col, pivot);
+        for row in col + 1..4 {
+            let f = m[row][col] / m[col][col];
+            let pivot_row = m[col];
+            for (x, p) in m[row].iter_mut().zip(pivot_row).skip(col) {
+                *x -= f * p;
+            }
+            y[row] -= f * y[col];
+        }
+    }
+
+    let mut x = [0.0; 4];
+    for row in (0..4).rev() {
+        let s: f64 = (row + 1..4).map(|k| m[row][k] * x[k]).sum();
+        x[row] = (y[row] - s) / m[row][row];
+    }
+    Some(x)
+}
+
+/// (空きマス数, 深さ) ごとのあてはめの結果から、`EvalSearchMpcModel` の係数を最小二乗法で求める
+///
+/// 各組の重みは同じとする。説明変数が足りない (深さが1通りしか無いなど) 場合は `None` を返す。
+pub fn fit_eval_search_model(fits: &[(i32, i32, LineFit)]) -> Option<EvalSearchMpcModel> {
+    let mut xtx = [[0.0; 4]; 4];
+    let mut xty = [[0.0; 4]; 3];
+    for &(n_empties, lv, fit) in fits {
+        let x = [1.0, n_empties as f64, lv as f64, EVAL_SEARCH_MPC_SEARCH_LV[lv as usize] as f64];
+        for i in 0..4 {
+            for j in 0..4 {
+                xtx[i][j] += x[i] * x[j];
+            }
+            xty[0][i] += x[i] * fit.a;
+            xty[1][i] += x[i] * fit.b;
+            xty[2][i] += x[i] * fit.e_std;
+        }
+    }
+
+    Some(EvalSearchMpcModel {
+        a: solve_linear_system(xtx, xty[0])?,
+        b: solve_linear_system(xtx, xty[1])?,
+        e_std: solve_linear_system(xtx, xty[2])?,
+    })
+}
+
+/// 値の組を (空きマス数, 深さ) ごとに分けて、それぞれに直線をあてはめる
+///
+/// 戻り値は (空きマス数, 深さ, 浅い探索の深さ, あてはめの結果) で、局面の足りない組は含まない。
+pub fn fit_groups(samples: &[MpcSample]) -> Vec<(i32, i32, i32, LineFit)> {
+    // (空きマス数, 深さ, 浅い探索の深さ, (v, V) の組)
+    type Group = (i32, i32, i32, Vec<(f64, f64)>);
+    let mut groups: Vec<Group> = Vec::new();
+    for s in samples {
+        match groups.iter_mut().find(|g| g.0 == s.n_empties && g.1 == s.lv && g.2 == s.mpc_lv) {
+            Some(g) => g.3.push((s.shallow as f64, s.deep as f64)),
+            None => groups.push((s.n_empties, s.lv, s.mpc_lv, vec![(s.shallow as f64, s.deep as f64)])),
+        }
+    }
+    groups.sort_by_key(|g| (g.0, g.1, g.2));
+
+    groups
+        .into_iter()
+        .filter_map(|(n_empties, lv, mpc_lv, points)| Some((n_empties, lv, mpc_lv, fit_line(&points)?)))
+        .collect()
+}
+
+/// 値の組からパラメータ表を求める
+///
+/// 局面が足りずに求められなかったパラメータは `init` の値のまま残す。
+/// 浅い探索と深い探索の値が全て一致した組 (e_std が 0) や、探索に使えないパラメータも書き込まない。
+pub fn calibrate(init: &MpcTable, samples: &MpcSamples) -> MpcTable {
+    let mut table = init.clone();
+
+    let eval_fits: Vec<(i32, i32, LineFit)> = fit_groups(&samples.eval_search)
+        .into_iter()
+        .filter(|(_, _, _, fit)| fit.e_std > 0.0)
+        .map(|(n, lv, _, fit)| (n, lv, fit))
+        .collect();
+    if let Some(model) = fit_eval_search_model(&eval_fits) {
+        if model.validate().is_ok() {
+            table.eval_search = model;
+        }
+    }
+
+    for (n_empties, _, mpc_lv, fit) in fit_groups(&samples.perfect_search) {
+        let params = MpcParams { lv: mpc_lv, a: fit.a, b: fit.b, e_std: fit.e_std };
+        if params.is_valid() {
+            table.perfect_search[n_empties as usize] = Some(params);
+        }
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_line_recovers_the_line() {
+        // V = 2v + 1 に、同じ v で +1 と -1 の誤差を加えた点
+        let points: Vec<(f64, f64)> = (0..10)
+            .flat_map(|v| {
+                let v = v as f64;
+                [(v, 2.0 * v + 2.0), (v, 2.0 * v)]
+            })
+            .collect();
+        let fit = fit_line(&points).unwrap();
+        assert!((fit.a - 2.0).abs() < 1e-9);
+        assert!((fit.b - 1.0).abs() < 1e-9);
+        assert!((fit.e_std - (20.0f64 / 18.0).sqrt()).abs() < 1e-9);
+
+        assert!(fit_line(&points[..MIN_GROUP_SAMPLES - 1]).is_none());
+        assert!(fit_line(&[(3.0, 1.0); MIN_GROUP_SAMPLES]).is_none());
+    }
+
+    #[test]
+    fn eval_search_model_is_recovered() {
+        let mut fits = Vec::new();
+        for n_empties in 20..40 {
+            for lv in 4..=10 {
+                let p = EVAL_SEARCH_MPC_MODEL.params(lv, n_empties);
+                fits.push((n_empties, lv, LineFit { n: 100, a: p.a, b: p.b, e_std: p.e_std }));
+            }
+        }
+        let model = fit_eval_search_model(&fits).unwrap();
+        for (fitted, expected) in [
+            (model.a, EVAL_SEARCH_MPC_MODEL.a),
+            (model.b, EVAL_SEARCH_MPC_MODEL.b),
+            (model.e_std, EVAL_SEARCH_MPC_MODEL.e_std),
+        ] {
+            for (x, y) in fitted.iter().zip(&expected) {
+                assert!((x - y).abs() < 1e-6);
+            }
+        }
+
+        // 深さが1通りでは、深さの係数が決まらない
+        let single_level: Vec<_> = fits.into_iter().filter(|f| f.1 == 6).collect();
+        assert!(fit_eval_search_model(&single_level).is_none());
+    }
+
+    #[test]
+    fn calibrate_skips_fits_without_error() {
+        // 浅い探索と深い探索の値が全て一致する (e_std が 0 になる) 組
+        let pairs = |n_empties: i32, lv: i32, mpc_lv: i32| -> Vec<MpcSample> {
+            (0..MIN_GROUP_SAMPLES as i32 * 2)
+                .map(|i| MpcSample { n_empties, lv, mpc_lv, deep: i * 2 - 10, shallow: i * 2 - 10 })
+                .collect()
+        };
+        let init = MpcTable::default();
+        let mut samples = MpcSamples {
+            perfect_search: pairs(14, 14, init.perfect_search[14].unwrap().lv),
+            ..Default::default()
+        };
+        for n_empties in 20..30 {
+            for lv in 4..=10 {
+                samples.eval_search.extend(pairs(n_empties, lv, EVAL_SEARCH_MPC_SEARCH_LV[lv as usize]));
+            }
+        }
+
+        let table = calibrate(&init, &samples);
+        assert_eq!(table.perfect_search[14], init.perfect_search[14]);
+        assert_eq!(table.eval_search, init.eval_search);
+        assert!(table.validate().is_ok());
+    }
+}
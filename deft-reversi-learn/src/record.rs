@@ -0,0 +1,139 @@
+// 棋譜ファイルの読み込みと、学習局面の生成
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use deft_reversi_engine::perfect_search::solve_score;
+use deft_reversi_engine::*;
+
+/// 学習に用いる局面と、その正解の評価値
+#[derive(Clone)]
+pub struct TrainingPosition {
+    pub board: Board,
+    /// 手番側から見た石差
+    pub score: i32,
+}
+
+/// 棋譜 (またはデータセット) のファイルのパスを集める
+///
+/// ディレクトリが指定された場合は、その直下のファイルを名前順に加える。
+pub fn collect_files(paths: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut entries = fs::read_dir(path)?
+                .map(|entry| entry.map(|e| e.path()))
+                .collect::<io::Result<Vec<_>>>()?;
+            entries.retain(|p| p.is_file());
+            entries.sort();
+            files.extend(entries);
+        } else {
+            files.push(path.clone());
+        }
+    }
+    Ok(files)
+}
+
+/// 棋譜ファイル (1行に1試合) を読み込み、各試合を再生する
+///
+/// 空行と `#` で始まる行は無視する。
+/// 不正な棋譜は読み飛ばし、その数を `n_skipped` に加える。
+pub fn read_record_file(path: &Path, n_skipped: &mut usize) -> io::Result<Vec<ReplayedGame>> {
+    let contents = fs::read_to_string(path)?;
+    let mut games = Vec::new();
+    for (line_no, line) in contents.lines().enumerate() {
+        let record = line.trim();
+        if record.is_empty() || record.starts_with('#') {
+            continue;
+        }
+        match replay_record(record) {
+            Ok(game) => games.push(game),
+            Err(e) => {
+                eprintln!("skip {}:{} ({})", path.display(), line_no + 1, e);
+                *n_skipped += 1;
+            }
+        }
+    }
+    Ok(games)
+}
+
+/// 棋譜を再生した試合
+pub struct ReplayedGame {
+    /// 着手できる局面とその手番 (初手から順に, パスした局面と終局を除く)
+    pub positions: Vec<(Board, Color)>,
+    /// 終局していれば、黒から見た最終石差
+    pub score_black: Option<i32>,
+}
+
+/// 棋譜 ("f5d6c3..." の形式) を `Game::from_record` で再生する
+///
+/// パスは棋譜に含まれないため、着手できない場合は自動的にパスする。
+pub fn replay_record(record: &str) -> Result<ReplayedGame, &'static str> {
+    let mut game = Game::from_record(record)?;
+
+    let score_black = if game.is_end() {
+        let score = solve_score(&game.current.board);
+        Some(if matches!(game.current.turn, Color::Black) { score } else { -score })
+    } else {
+        None
+    };
+
+    // 終局から初手まで戻りながら局面を集める
+    let mut positions = Vec::with_capacity(60);
+    loop {
+        if game.current.board.moves() != 0 {
+            positions.push((game.current.board.clone(), game.current.turn));
+        }
+        if game.undo().is_err() {
+            break;
+        }
+    }
+    positions.reverse();
+
+    Ok(ReplayedGame { positions, score_black })
+}
+
+impl ReplayedGame {
+    /// 各局面に手番側から見た最終石差を付ける (終局していない場合はエラー)
+    pub fn training_positions(self) -> Result<Vec<TrainingPosition>, &'static str> {
+        let score_black = self.score_black.ok_or("the game is not finished")?;
+
+        Ok(self
+            .positions
+            .into_iter()
+            .map(|(board, turn)| TrainingPosition {
+                board,
+                score: if matches!(turn, Color::Black) { score_black } else { -score_black },
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions_from_record(record: &str) -> Result<Vec<TrainingPosition>, &'static str> {
+        replay_record(record)?.training_positions()
+    }
+
+    #[test]
+    fn labels_every_position_with_the_final_score() {
+        // 白の全滅で終わる最短の試合
+        let record = "d3c3b3d2e1d6d7e3f4";
+        let positions = positions_from_record(record).unwrap();
+
+        assert_eq!(positions.len(), 9);
+        assert!(positions[0].board == Board::new());
+        for (i, p) in positions.iter().enumerate() {
+            assert_eq!(p.board.move_count(), i as i32);
+            // 黒の勝ち (64-0)
+            let expected = if i % 2 == 0 { 64 } else { -64 };
+            assert_eq!(p.score, expected);
+        }
+
+        assert!(positions_from_record("f5d6").is_err());
+        assert!(positions_from_record("f5f5").is_err());
+    }
+}
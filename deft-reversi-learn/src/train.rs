@@ -0,0 +1,128 @@
+// 評価関数の学習 (確率的勾配降下法による線形回帰)
+
+use rand::prelude::*;
+
+use deft_reversi_engine::*;
+
+use crate::eval_for_learn::EvaluatorForLearn;
+use crate::record::TrainingPosition;
+
+/// 学習の設定
+#[derive(Clone, Debug)]
+pub struct TrainConfig {
+    pub n_epochs: usize,
+    pub learning_rate: f64,
+    /// L2 正則化の係数
+    pub lambda: f64,
+    /// 序盤 (`OPENING_MOVE_COUNT` 手未満) の学習率に掛ける係数
+    ///
+    /// 序盤の局面は最終石差との相関が弱いため、学習率を下げる。
+    pub opening_rate_scale: f64,
+}
+
+impl Default for TrainConfig {
+    fn default() -> Self {
+        Self {
+            n_epochs: 20,
+            learning_rate: 0.0005,
+            lambda: 0.000002,
+            opening_rate_scale: 1.0 / 3.0,
+        }
+    }
+}
+
+/// `TrainConfig::opening_rate_scale` を適用する手数
+pub const OPENING_MOVE_COUNT: i32 = 20;
+
+/// 1エポックの、手数ごとの平均二乗誤差
+pub struct EpochError {
+    pub mse: [f64; 61],
+    pub count: [usize; 61],
+}
+
+impl EpochError {
+    fn new() -> Self {
+        Self { mse: [0.0; 61], count: [0; 61] }
+    }
+
+    fn add(&mut self, move_count: usize, error: f64) {
+        self.mse[move_count] += error;
+        self.count[move_count] += 1;
+    }
+
+    fn finish(&mut self) {
+        for (e, &n) in self.mse.iter_mut().zip(&self.count) {
+            if n != 0 {
+                *e /= n as f64;
+            }
+        }
+    }
+
+    /// 全局面の平均二乗誤差
+    pub fn total(&self) -> f64 {
+        let n: usize = self.count.iter().sum();
+        if n == 0 {
+            return 0.0;
+        }
+        self.mse.iter().zip(&self.count).map(|(&e, &c)| e * c as f64).sum::<f64>() / n as f64
+    }
+}
+
+/// 学習局面をシャッフルしながら `config.n_epochs` 回学習する
+///
+/// 各エポックの終わりに `on_epoch` を呼ぶ (進捗の表示用)。
+pub fn train<F>(
+    evaluator: &mut EvaluatorForLearn,
+    positions: &mut [TrainingPosition],
+    config: &TrainConfig,
+    mut on_epoch: F,
+) where
+    F: FnMut(usize, &EpochError),
+{
+    let mut rng = thread_rng();
+    let mut scratch = FeatureScratch::new(FeatureExtractor::default());
+
+    for epoch in 1..=config.n_epochs {
+        positions.shuffle(&mut rng);
+        let mut error = EpochError::new();
+
+        for position in positions.iter() {
+            let board = &position.board;
+            let move_count = board.move_count();
+            let learning_rate = if move_count < OPENING_MOVE_COUNT {
+                config.learning_rate * config.opening_rate_scale
+            } else {
+                config.learning_rate
+            };
+
+            scratch.clac_features(board);
+            let diff = evaluator.clac_eval(board, &scratch.feature_bit) - position.score as f64;
+            evaluator.update(board, &scratch.feature_bit, diff, learning_rate, config.lambda);
+
+            error.add(move_count as usize, diff * diff);
+        }
+
+        error.finish();
+        evaluator.n_iteration += 1;
+        on_epoch(epoch, &error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::replay_record;
+
+    #[test]
+    fn training_reduces_error() {
+        let mut positions = replay_record("d3c3b3d2e1d6d7e3f4").unwrap().training_positions().unwrap();
+        let mut evaluator = EvaluatorForLearn::new();
+        let config = TrainConfig { n_epochs: 10, ..TrainConfig::default() };
+
+        let mut errors = Vec::new();
+        train(&mut evaluator, &mut positions, &config, |_, e| errors.push(e.total()));
+
+        assert_eq!(evaluator.n_iteration, 10);
+        assert!(errors.last().unwrap() < errors.first().unwrap());
+    }
+}
@@ -10,7 +10,8 @@ async function fetch_eval_data() {
         }
 
         const data = await response.arrayBuffer();
-        const decompressedData = await pako.ungzip(data, { to: 'string' });
+        // 評価関数のバイナリ形式・JSON 形式のどちらでもよい (Rust 側で判別する)
+        const decompressedData = pako.ungzip(new Uint8Array(data));
 
         // 将来的にこちらを使う
         // const data = await response.blob();
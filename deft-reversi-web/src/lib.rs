@@ -61,8 +61,9 @@ pub struct StateForJS {
 impl App {
     #[allow(clippy::new_without_default)]
     // #[wasm_bindgen(constructor)]
-    pub async fn new(eval_string: &str, opening_string: &str) -> Self {
-        let evaluator = match Evaluator::read_string(eval_string) {
+    /// `eval_data` は評価関数のバイナリ形式または JSON 形式のデータ (形式は自動で判別する)
+    pub async fn new(eval_data: &[u8], opening_string: &str) -> Self {
+        let evaluator = match Evaluator::read_bytes(eval_data) {
             Ok(e) => e,
             Err(e) => {
                 console_log!("Evaluatorを読み込む際にエラーが置きました。{}",e);
@@ -78,7 +79,8 @@ impl App {
         };
         Self {
             game: Game::new(),
-            solver: Solver::new(evaluator),
+            // wasm ではメモリが限られるため、置換表はネイティブ版 (96 MB) より小さくする
+            solver: Solver::with_t_table_size_mb(evaluator, 24),
             opening_book,
             rng: rand::thread_rng(),
             lv: 1,
@@ -111,17 +113,9 @@ impl App {
     fn get_move_scores(&mut self, lv: i32) -> [i32; 64] {
         let mut scores = [0; 64];
         let b = &self.game.current.board;
-        let legal_moves =  b.moves();
-        for i in 0..64 {
-            let mask = 1u64 << i;
-            if mask & legal_moves != 0 {
-                let position = position_bit_to_num(mask).unwrap();
-                let mut b = b.clone();
-                b.put(mask);
-                let result = self.solver.solve(&b, lv);                
-                scores[position as usize] = -result.eval;
-                
-            }
+        let n_moves = b.moves().count_ones() as usize;
+        for m in self.solver.solve_multi_pv(b, lv, n_moves) {
+            scores[m.put_place as usize] = m.score;
         }
         scores
     }
@@ -260,6 +254,7 @@ impl App {
         console_log!("    score         : {:+}", r.eval);
         console_log!("    empty squares : {  }", empties);
         console_log!("    best move     : {  }", position_bit_to_str(r.best_move).unwrap());
+        console_log!("    pv            : {  }", r.pv_string());
         console_log!("    node          : {  }", r.searched_nodes);
     }
 